@@ -0,0 +1,222 @@
+use std::{
+    any::Any,
+    error::Error,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::cli::Job;
+use crate::export::save_to_path;
+
+pub const DEFAULT_TEMPLATE: &str = "{stem}.{ext}";
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub input_dir: PathBuf,
+    pub output_dir: PathBuf,
+    // Nome dos arquivos de saida: {stem}, {ext}, {name} e {index} sao substituidos
+    pub template: String,
+    // Nome da operacao ou receita usado em {name}
+    pub name: String,
+    pub threads: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub processed: Vec<PathBuf>,
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+// Imagens do diretorio que o crate image sabe abrir, em ordem alfabetica
+pub fn list_images(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut images = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && image::ImageFormat::from_path(&path).is_ok() {
+            images.push(path);
+        }
+    }
+
+    images.sort();
+    Ok(images)
+}
+
+pub fn output_name(template: &str, input: &Path, name: &str, index: usize) -> String {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext = input.extension().and_then(|s| s.to_str()).unwrap_or("png");
+
+    template
+        .replace("{stem}", stem)
+        .replace("{ext}", ext)
+        .replace("{name}", name)
+        .replace("{index}", &index.to_string())
+}
+
+fn process(job: &Job, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let image = image::open(input)?;
+    save_to_path(&job.apply(&image), output)?;
+
+    Ok(())
+}
+
+// Mensagem passada para panic!, quando for um texto
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "erro desconhecido".to_owned())
+}
+
+// Aplica o trabalho em todas as imagens do diretorio, dividindo os arquivos entre `threads`
+// threads. `progress` e chamado apos cada arquivo com (concluidos, total, arquivo)
+pub fn run_batch(
+    job: &Job,
+    options: &BatchOptions,
+    progress: impl Fn(usize, usize, &Path) + Sync,
+) -> io::Result<BatchReport> {
+    let images = list_images(&options.input_dir)?;
+    fs::create_dir_all(&options.output_dir)?;
+
+    let total = images.len();
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let report = Mutex::new(BatchReport::default());
+
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1).min(total.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let input = match images.get(index) {
+                    Some(input) => input,
+                    None => break,
+                };
+
+                let output = options.output_dir.join(output_name(
+                    &options.template,
+                    input,
+                    &options.name,
+                    index,
+                ));
+                // um panic ao processar um arquivo entra no relatorio em vez de
+                // derrubar o lote inteiro
+                let result = panic::catch_unwind(AssertUnwindSafe(|| process(job, input, &output)));
+
+                {
+                    let mut report = report.lock().unwrap();
+                    match result {
+                        Ok(Ok(())) => report.processed.push(output),
+                        Ok(Err(err)) => report.errors.push((input.clone(), err.to_string())),
+                        Err(payload) => report.errors.push((
+                            input.clone(),
+                            format!("falha ao processar: {}", panic_message(payload.as_ref())),
+                        )),
+                    }
+                }
+
+                progress(done.fetch_add(1, Ordering::SeqCst) + 1, total, input);
+            });
+        }
+    });
+
+    let mut report = report.into_inner().unwrap();
+    report.processed.sort();
+    report.errors.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use crate::batch::{list_images, output_name, run_batch, BatchOptions};
+    use crate::cli::Job;
+    use crate::imageops::Operation;
+    use crate::imageops2::{Kernel, Normalization};
+
+    #[test]
+    fn test_batch() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            output_name(
+                "{name}-{stem}-{index}.png",
+                Path::new("a/b.jpg"),
+                "negative",
+                3
+            ),
+            "negative-b-3.png"
+        );
+
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        // diretorio com duas imagens e um arquivo corrompido
+        let temp_dir = std::env::temp_dir().join(format!("fpi-batch-{}", std::process::id()));
+        let input_dir = temp_dir.join("in");
+        let output_dir = temp_dir.join("out");
+        let _ = std::fs::remove_dir_all(&input_dir);
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&input_dir)?;
+
+        let img = image::open(image_folder_path.join("Gramado_22k.jpg"))?;
+        img.save(input_dir.join("a.png"))?;
+        img.save(input_dir.join("b.png"))?;
+        std::fs::write(input_dir.join("c.png"), b"nao e uma imagem")?;
+        std::fs::write(input_dir.join("notas.txt"), b"ignorado")?;
+        assert_eq!(list_images(&input_dir)?.len(), 3);
+
+        let options = BatchOptions {
+            input_dir: input_dir.clone(),
+            output_dir: output_dir.clone(),
+            template: "{stem}-{name}.png".to_owned(),
+            name: "negative".to_owned(),
+            threads: 2,
+        };
+
+        let job = Job::Operation(Operation::Negative, Box::default());
+        let calls = AtomicUsize::new(0);
+        let report = run_batch(&job, &options, |_, total, _| {
+            assert_eq!(total, 3);
+            calls.fetch_add(1, Ordering::SeqCst);
+        })?;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(report.processed.len(), 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, input_dir.join("c.png"));
+        assert!(output_dir.join("a-negative.png").exists());
+        assert!(output_dir.join("b-negative.png").exists());
+
+        // um arquivo que causa panic vira um erro do relatorio e os demais continuam
+        let broken = Kernel::Dense {
+            width: 0,
+            height: 1,
+            weights: Arc::new(vec![1.0]),
+        };
+        let job = Job::Operation(
+            Operation::Convolution(broken, Normalization::Clamp),
+            Box::default(),
+        );
+        let report = run_batch(&job, &options, |_, _, _| {})?;
+        assert!(report.processed.is_empty());
+        assert_eq!(report.errors.len(), 3);
+        assert!(report.errors[0].1.starts_with("falha ao processar"));
+
+        std::fs::remove_dir_all(temp_dir)?;
+        Ok(())
+    }
+}
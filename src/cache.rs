@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use crate::{apply_operation, history::Step, imageops::Operation, imageops2::ImageExt2, AppState};
+
+// Imagem pronta para exibir, com o histograma ja renderizado
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub image: Arc<DynamicImage>,
+    pub histogram: Arc<DynamicImage>,
+    // Maior coluna do histograma, usada no rotulo
+    pub max_count: u32,
+}
+
+impl Rendered {
+    pub fn new(image: DynamicImage) -> Self {
+        let max_count = *image.grayscale_histogram().iter().max().unwrap_or(&0);
+
+        Self {
+            histogram: Arc::new(image.render_grayscale_histogram()),
+            image: Arc::new(image),
+            max_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedImages {
+    pub original: Rendered,
+    // Resultado do historico, entrada da operacao selecionada
+    pub current: Arc<DynamicImage>,
+    pub result: Rendered,
+}
+
+// Tudo o que afeta o processamento; outras mudancas no estado nao geram trabalho novo
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderKey {
+    pub image: Option<String>,
+    pub history: Vec<Step>,
+    pub step: Step,
+}
+
+impl RenderKey {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            image: state.selected_image.clone(),
+            history: state.history.applied().to_vec(),
+            step: Step::from_state(state.selected_operation.clone(), state),
+        }
+    }
+}
+
+// Processamento interrompido porque um pedido mais novo chegou
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cancelled;
+
+// Guarda cada etapa do processamento e so recalcula as que dependem do que mudou:
+// trocar a imagem reabre o arquivo, mudar o historico reaplica os passos e mudar a
+// operacao ou seus parametros reaplica apenas a operacao selecionada
+#[derive(Default)]
+pub struct ImageCache {
+    original: Option<(String, Result<Rendered, String>)>,
+    current: Option<(Vec<Step>, Arc<DynamicImage>)>,
+    result: Option<(Step, Rendered)>,
+    // Quantas vezes cada etapa foi calculada
+    pub loads: usize,
+    pub history_runs: usize,
+    pub operation_runs: usize,
+}
+
+impl ImageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, state: &AppState) -> Option<Result<RenderedImages, String>> {
+        self.update_until(state, &|| false).unwrap_or(None)
+    }
+
+    // Como `update`, mas verifica `cancelled` entre os passos do historico e do pipeline
+    // e desiste do processamento quando ele devolve true
+    pub fn update_until(
+        &mut self,
+        state: &AppState,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Result<Option<Result<RenderedImages, String>>, Cancelled> {
+        let path = match state.selected_image.as_ref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        if self.original.as_ref().map(|(p, _)| p) != Some(path) {
+            self.loads += 1;
+            let original = image::open(path)
+                .map(Rendered::new)
+                .map_err(|err| format!("Nao foi possivel abrir {}: {}", path, err));
+
+            self.original = Some((path.clone(), original));
+            self.current = None;
+            self.result = None;
+        }
+
+        let original = match &self.original {
+            Some((_, Ok(original))) => original,
+            Some((_, Err(err))) => return Ok(Some(Err(err.clone()))),
+            None => return Ok(None),
+        };
+
+        let steps = state.history.applied();
+        let current = match &self.current {
+            Some((s, current)) if s.as_slice() == steps => current.clone(),
+            _ => {
+                self.history_runs += 1;
+                let current = state
+                    .history
+                    .apply_until(&original.image, state, cancelled)
+                    .ok_or(Cancelled)?;
+                let current = Arc::new(current);
+
+                self.current = Some((steps.to_vec(), current.clone()));
+                self.result = None;
+                current
+            }
+        };
+
+        let step = Step::from_state(state.selected_operation.clone(), state);
+        if self.result.as_ref().map(|(s, _)| s) != Some(&step) {
+            self.operation_runs += 1;
+            let result = match &state.selected_operation {
+                Operation::Pipeline(pipeline) => pipeline
+                    .apply_until(&current, state, cancelled)
+                    .ok_or(Cancelled)?,
+                op => apply_operation(&current, op, state),
+            };
+
+            // o histograma do resultado nao e calculado para um pedido ultrapassado
+            if cancelled() {
+                return Err(Cancelled);
+            }
+
+            self.result = Some((step, Rendered::new(result)));
+        }
+
+        Ok(Some(Ok(RenderedImages {
+            original: original.clone(),
+            current,
+            result: self.result.as_ref().unwrap().1.clone(),
+        })))
+    }
+
+    // Ultimo resultado calculado, usado ao salvar
+    pub fn result(&self) -> Option<&DynamicImage> {
+        self.result
+            .as_ref()
+            .map(|(_, rendered)| rendered.image.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, path::Path};
+
+    use crate::cache::{Cancelled, ImageCache};
+    use crate::history::Step;
+    use crate::imageops::Operation;
+    use crate::imageops2::ImageExt2;
+    use crate::AppState;
+
+    #[test]
+    fn test_cache_recomputes_only_what_changed() {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let mut state = AppState::new();
+        let mut cache = ImageCache::new();
+        assert!(cache.update(&state).is_none());
+
+        state.selected_image = Some(
+            image_folder_path
+                .join("Gramado_22k.jpg")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        state.selected_operation = Operation::AdjustBrightness;
+        cache.update(&state).unwrap().unwrap();
+        cache.update(&state).unwrap().unwrap();
+        assert_eq!(
+            (cache.loads, cache.history_runs, cache.operation_runs),
+            (1, 1, 1)
+        );
+
+        // mudar algo que nao afeta o processamento nao recalcula nada
+        state.status = "outra mensagem".to_owned();
+        state.recipe_path = "outra.json".to_owned();
+        cache.update(&state).unwrap().unwrap();
+        assert_eq!(cache.operation_runs, 1);
+
+        state.param1 = 10.0;
+        let images = cache.update(&state).unwrap().unwrap();
+        let original = images.original.image.clone();
+        assert_eq!(*images.result.image, original.adjust_brightness(10));
+        assert_eq!(
+            (cache.loads, cache.history_runs, cache.operation_runs),
+            (1, 1, 2)
+        );
+
+        let step = Step::from_state(Operation::Negative, &state);
+        state.history.push(step);
+        cache.update(&state).unwrap().unwrap();
+        assert_eq!(
+            (cache.loads, cache.history_runs, cache.operation_runs),
+            (1, 2, 3)
+        );
+        assert_eq!(
+            cache.result(),
+            Some(&original.negative().adjust_brightness(10))
+        );
+
+        // um pedido cancelado para entre os passos do historico e nao guarda nada parcial
+        state
+            .history
+            .push(Step::from_state(Operation::FlipH, &state));
+        let checks = Cell::new(0);
+        let cancelled = || {
+            checks.set(checks.get() + 1);
+            checks.get() > 1
+        };
+        assert_eq!(
+            cache.update_until(&state, &cancelled).err(),
+            Some(Cancelled)
+        );
+        assert_eq!(checks.get(), 2);
+        cache.update(&state).unwrap().unwrap();
+        assert_eq!(
+            (cache.loads, cache.history_runs, cache.operation_runs),
+            (1, 4, 4)
+        );
+
+        state.selected_image = Some("nao-existe.png".to_owned());
+        assert!(cache.update(&state).unwrap().is_err());
+        assert_eq!(cache.loads, 2);
+    }
+}
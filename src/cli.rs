@@ -0,0 +1,482 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::{
+    apply_operation,
+    batch::{run_batch, BatchOptions, DEFAULT_TEMPLATE},
+    export::{save_to_path, SaveError},
+    imageops::{Interpolation, Operation},
+    imageops2::{
+        EqualizeMode, GradientOutput, Kernel, Normalization, GAUSSIANO, LAPLACIANO, PASSA_ALTAS,
+        PREWITT_HX, PREWITT_HY, SOBEL_HX, SOBEL_HY,
+    },
+    neutral_param1, param_ranges,
+    recipe::{Recipe, RecipeError},
+    AppState,
+};
+
+pub const USAGE: &str = "uso:
+    fpi                                              abre a interface grafica
+    fpi apply --op <operacao> --in <entrada> --out <saida> [opcoes]
+    fpi apply --recipe <receita.json> --in <entrada> --out <saida>
+    fpi batch (--op <operacao> | --recipe <receita.json>) --in-dir <dir> --out-dir <dir>
+              [--name-template {stem}-{name}.{ext}] [--threads <n>] [opcoes]
+
+opcoes:
+    --param1 <n>  --param2 <n>  --param3 <n>  --angle <graus>  --target <imagem>
+    --width <px>  --height <px>  (resize; um lado omitido segue a proporcao)
+
+operacoes:
+    negative, flip-h, flip-v, grayscale, quantize, brightness, contrast, gamma,
+    zoom-out, zoom-in, resize, equalize, equalize-rgb, equalize-luminance,
+    histogram-match, rotate-cw, rotate-ccw, rotate, rotate-bilinear,
+    rotate-bicubic, canny, gradient, gradient-edges, gradient-orientation,
+    gaussian, laplacian, high-pass, prewitt-x, prewitt-y, sobel-x, sobel-y";
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Recipe(RecipeError),
+    Image(image::ImageError),
+    Io(io::Error),
+    Save(SaveError),
+    // Quantidade de arquivos que falharam no modo batch
+    Batch(usize),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            CliError::Recipe(err) => write!(f, "{}", err),
+            CliError::Image(err) => write!(f, "erro de imagem: {}", err),
+            CliError::Io(err) => write!(f, "erro de arquivo: {}", err),
+            CliError::Save(err) => write!(f, "{}", err),
+            CliError::Batch(failed) => write!(f, "{} arquivo(s) falharam", failed),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<RecipeError> for CliError {
+    fn from(err: RecipeError) -> Self {
+        CliError::Recipe(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+impl From<SaveError> for CliError {
+    fn from(err: SaveError) -> Self {
+        CliError::Save(err)
+    }
+}
+
+impl From<image::ImageError> for CliError {
+    fn from(err: image::ImageError) -> Self {
+        CliError::Image(err)
+    }
+}
+
+// Operacao a partir do nome usado na linha de comando
+pub fn parse_operation(name: &str) -> Option<Operation> {
+    let conv = |kernel: [f32; 9], normalization| {
+        Operation::Convolution(Kernel::from(kernel), normalization)
+    };
+
+    let op = match name {
+        "none" => Operation::None,
+        "negative" => Operation::Negative,
+        "flip-h" => Operation::FlipH,
+        "flip-v" => Operation::FlipV,
+        "grayscale" => Operation::Grayscale,
+        "quantize" => Operation::Quantize,
+        "brightness" => Operation::AdjustBrightness,
+        "contrast" => Operation::AdjustContrast,
+        "gamma" => Operation::Gamma,
+        "zoom-out" => Operation::ZoomOut,
+        "zoom-in" => Operation::ZoomIn,
+        "resize" => Operation::Resize,
+        "equalize" => Operation::Equalize(EqualizeMode::Grayscale),
+        "equalize-rgb" => Operation::Equalize(EqualizeMode::PerChannel),
+        "equalize-luminance" => Operation::Equalize(EqualizeMode::Luminance),
+        "histogram-match" => Operation::HistogramMatch,
+        "rotate-cw" => Operation::RotateCw,
+        "rotate-ccw" => Operation::RotateCcw,
+        "rotate" => Operation::Rotate(Interpolation::Nearest),
+        "rotate-bilinear" => Operation::Rotate(Interpolation::Bilinear),
+        "rotate-bicubic" => Operation::Rotate(Interpolation::Bicubic),
+        "canny" => Operation::Canny,
+        "gradient" => Operation::Gradient(GradientOutput::Magnitude),
+        "gradient-edges" => Operation::Gradient(GradientOutput::Edges),
+        "gradient-orientation" => Operation::Gradient(GradientOutput::Orientation),
+        "gaussian" => conv(GAUSSIANO, Normalization::Clamp),
+        "laplacian" => conv(LAPLACIANO, Normalization::Clamp),
+        "high-pass" => conv(PASSA_ALTAS, Normalization::Clamp),
+        "prewitt-x" => conv(PREWITT_HX, Normalization::Offset127),
+        "prewitt-y" => conv(PREWITT_HY, Normalization::Offset127),
+        "sobel-x" => conv(SOBEL_HX, Normalization::Offset127),
+        "sobel-y" => conv(SOBEL_HY, Normalization::Offset127),
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+// O que aplicar em cada imagem: uma operacao com os parametros do estado, ou uma receita
+#[derive(Debug, Clone)]
+pub enum Job {
+    Operation(Operation, Box<AppState>),
+    Recipe(Recipe),
+}
+
+impl Job {
+    pub fn apply(&self, image: &image::DynamicImage) -> image::DynamicImage {
+        match self {
+            Job::Operation(op, state) => apply_operation(image, op, state),
+            Job::Recipe(recipe) => recipe.apply(image),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ApplyArgs {
+    pub job: Job,
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+// Le os pares `--chave valor` restantes dos argumentos
+fn parse_flags(args: &[String]) -> Result<Vec<(String, String)>, CliError> {
+    let mut flags = vec![];
+    let mut iter = args.iter();
+
+    while let Some(flag) = iter.next() {
+        let key = flag
+            .strip_prefix("--")
+            .ok_or_else(|| CliError::Usage(format!("argumento inesperado: {}", flag)))?;
+        let value = iter
+            .next()
+            .ok_or_else(|| CliError::Usage(format!("faltando valor para --{}", key)))?;
+
+        flags.push((key.to_owned(), value.clone()));
+    }
+
+    Ok(flags)
+}
+
+fn parse_number(key: &str, value: &str) -> Result<f64, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::Usage(format!("valor invalido para --{}: {}", key, value)))
+}
+
+// Monta o trabalho a partir das opcoes --op/--recipe e dos parametros,
+// devolvendo as opcoes que nao foram usadas
+pub fn parse_job(flags: Vec<(String, String)>) -> Result<(Job, Vec<(String, String)>), CliError> {
+    let mut state = AppState::new();
+    let mut op = None;
+    let mut recipe = None;
+    let mut rest = vec![];
+    let mut param1 = None;
+
+    for (key, value) in flags {
+        match key.as_str() {
+            "op" => {
+                op =
+                    Some(parse_operation(&value).ok_or_else(|| {
+                        CliError::Usage(format!("operacao desconhecida: {}", value))
+                    })?)
+            }
+            "recipe" => recipe = Some(Recipe::load(&value)?),
+            "param1" => param1 = Some(parse_number(&key, &value)?),
+            "param2" => state.param2 = parse_number(&key, &value)?,
+            "param3" => state.param3 = parse_number(&key, &value)?,
+            "angle" => state.angle = parse_number(&key, &value)?,
+            "width" => state.resize_width = parse_number(&key, &value)? as u32,
+            "height" => state.resize_height = parse_number(&key, &value)? as u32,
+            "target" => {
+                // um alvo que nao abre seria ignorado em silencio pelo casamento de histograma
+                image::image_dimensions(&value)?;
+                state.target_image = Some(value)
+            }
+            _ => rest.push((key, value)),
+        }
+    }
+
+    let job = match (op, recipe) {
+        (Some(Operation::HistogramMatch), None) if state.target_image.is_none() => {
+            return Err(CliError::Usage(
+                "histogram-match precisa de --target".to_owned(),
+            ))
+        }
+        (Some(op), None) => {
+            // sem --param1, brilho, contraste e gamma usam o valor neutro
+            state.param1 = param1
+                .or_else(|| neutral_param1(&op))
+                .unwrap_or(state.param1);
+
+            // mesmas faixas dos sliders da interface; fora delas o zoom estoura o tamanho
+            // da imagem e o canny gera um kernel com NaN
+            let params = [state.param1, state.param2, state.param3];
+            for (idx, (value, (min, max))) in params.iter().zip(param_ranges(&op)).enumerate() {
+                if !(min..=max).contains(value) {
+                    return Err(CliError::Usage(format!(
+                        "--param{} deve estar entre {} e {}: {}",
+                        idx + 1,
+                        min,
+                        max,
+                        value
+                    )));
+                }
+            }
+
+            state.selected_operation = op.clone();
+            Job::Operation(op, Box::new(state))
+        }
+        (None, Some(recipe)) => Job::Recipe(recipe),
+        (Some(_), Some(_)) => {
+            return Err(CliError::Usage(
+                "use apenas uma entre --op e --recipe".to_owned(),
+            ))
+        }
+        (None, None) => return Err(CliError::Usage("faltando --op ou --recipe".to_owned())),
+    };
+
+    Ok((job, rest))
+}
+
+pub fn parse_apply(args: &[String]) -> Result<ApplyArgs, CliError> {
+    let (job, rest) = parse_job(parse_flags(args)?)?;
+    let mut input = None;
+    let mut output = None;
+
+    for (key, value) in rest {
+        match key.as_str() {
+            "in" => input = Some(PathBuf::from(value)),
+            "out" => output = Some(PathBuf::from(value)),
+            _ => return Err(CliError::Usage(format!("opcao desconhecida: --{}", key))),
+        }
+    }
+
+    Ok(ApplyArgs {
+        job,
+        input: input.ok_or_else(|| CliError::Usage("faltando --in".to_owned()))?,
+        output: output.ok_or_else(|| CliError::Usage("faltando --out".to_owned()))?,
+    })
+}
+
+pub fn parse_batch(args: &[String]) -> Result<(Job, BatchOptions), CliError> {
+    let flags = parse_flags(args)?;
+    // nome usado em {name}: a operacao ou o nome do arquivo da receita
+    let name = flags
+        .iter()
+        .find(|(key, _)| key == "op" || key == "recipe")
+        .map(|(_, value)| {
+            std::path::Path::new(value)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(value)
+                .to_owned()
+        })
+        .unwrap_or_default();
+
+    let (job, rest) = parse_job(flags)?;
+    let mut input_dir = None;
+    let mut output_dir = None;
+    let mut template = DEFAULT_TEMPLATE.to_owned();
+    let mut threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    for (key, value) in rest {
+        match key.as_str() {
+            "in-dir" => input_dir = Some(PathBuf::from(value)),
+            "out-dir" => output_dir = Some(PathBuf::from(value)),
+            "name-template" => template = value,
+            "threads" => threads = parse_number(&key, &value)? as usize,
+            _ => return Err(CliError::Usage(format!("opcao desconhecida: --{}", key))),
+        }
+    }
+
+    let options = BatchOptions {
+        input_dir: input_dir.ok_or_else(|| CliError::Usage("faltando --in-dir".to_owned()))?,
+        output_dir: output_dir.ok_or_else(|| CliError::Usage("faltando --out-dir".to_owned()))?,
+        template,
+        name,
+        threads,
+    };
+
+    Ok((job, options))
+}
+
+pub fn batch(job: &Job, options: &BatchOptions) -> Result<(), CliError> {
+    let report = run_batch(job, options, |done, total, input| {
+        eprintln!("[{}/{}] {}", done, total, input.display());
+    })?;
+
+    println!("{} imagem(ns) processada(s)", report.processed.len());
+    if report.errors.is_empty() {
+        return Ok(());
+    }
+
+    println!("erros:");
+    for (input, err) in report.errors.iter() {
+        println!("    {}: {}", input.display(), err);
+    }
+
+    Err(CliError::Batch(report.errors.len()))
+}
+
+pub fn apply(args: &ApplyArgs) -> Result<(), CliError> {
+    let image = image::open(&args.input)?;
+    save_to_path(&args.job.apply(&image), &args.output)?;
+
+    Ok(())
+}
+
+// Executa o subcomando, os argumentos nao incluem o nome do programa
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.split_first() {
+        Some((cmd, rest)) if cmd == "apply" => apply(&parse_apply(rest)?),
+        Some((cmd, rest)) if cmd == "batch" => {
+            let (job, options) = parse_batch(rest)?;
+            batch(&job, &options)
+        }
+        Some((cmd, _)) if cmd == "help" || cmd == "--help" || cmd == "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some((cmd, _)) => Err(CliError::Usage(format!("comando desconhecido: {}", cmd))),
+        None => Err(CliError::Usage("faltando comando".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, path::Path};
+
+    use crate::cli::{parse_apply, parse_batch, parse_operation, run, CliError, Job};
+    use crate::imageops::Operation;
+    use crate::imageops2::ImageExt2;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_cli_apply() -> Result<(), Box<dyn Error>> {
+        let parsed = parse_apply(&args("--op brightness --param1 40 --in a.jpg --out b.png"))?;
+        match &parsed.job {
+            Job::Operation(op, state) => {
+                assert_eq!(*op, Operation::AdjustBrightness);
+                assert_eq!(state.param1, 40.0);
+            }
+            Job::Recipe(_) => panic!("esperava uma operacao"),
+        }
+        assert_eq!(parsed.output, Path::new("b.png"));
+
+        let parsed = parse_apply(&args("--op contrast --in a.jpg --out b.png"))?;
+        match &parsed.job {
+            Job::Operation(_, state) => assert_eq!(state.param1, 1.0),
+            Job::Recipe(_) => panic!("esperava uma operacao"),
+        }
+        let parsed = parse_apply(&args("--op resize --width 300 --in a.jpg --out b.png"))?;
+        match &parsed.job {
+            Job::Operation(op, state) => {
+                assert_eq!(*op, Operation::Resize);
+                assert_eq!((state.resize_width, state.resize_height), (300, 0));
+            }
+            Job::Recipe(_) => panic!("esperava uma operacao"),
+        }
+        let parsed = parse_apply(&args("--op gamma --in a.jpg --out b.png"))?;
+        match &parsed.job {
+            Job::Operation(_, state) => assert_eq!(state.param1, 1.0),
+            Job::Recipe(_) => panic!("esperava uma operacao"),
+        }
+
+        let (_, options) = parse_batch(&args(
+            "--op sobel-x --in-dir in --out-dir out --name-template {name}-{stem}.png --threads 3",
+        ))?;
+        assert_eq!(options.name, "sobel-x");
+        assert_eq!(options.threads, 3);
+        assert_eq!(options.template, "{name}-{stem}.png");
+        assert!(matches!(
+            parse_batch(&args("--op negative --in-dir in")),
+            Err(CliError::Usage(_))
+        ));
+
+        assert!(parse_operation("sobel-x").is_some());
+        assert!(parse_operation("sebel").is_none());
+        assert!(matches!(
+            parse_apply(&args("--op negative --in a.jpg")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_apply(&args("--op negative --param1 abc --in a.jpg --out b.png")),
+            Err(CliError::Usage(_))
+        ));
+
+        for line in [
+            "--op zoom-out --param2 0 --in a.jpg --out b.png",
+            "--op canny --param3 0 --in a.jpg --out b.png",
+            "--op gamma --param1 nan --in a.jpg --out b.png",
+        ]
+        .iter()
+        {
+            assert!(matches!(parse_apply(&args(line)), Err(CliError::Usage(_))));
+        }
+        assert!(matches!(
+            parse_apply(&args("--op histogram-match --in a.jpg --out b.png")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_apply(&args(
+                "--op histogram-match --target nao-existe.png --in a.jpg --out b.png"
+            )),
+            Err(CliError::Image(_))
+        ));
+
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let input = image_folder_path.join("Gramado_22k.jpg");
+        let temp_dir = std::env::temp_dir().join(format!("fpi-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&temp_dir)?;
+        let output = temp_dir.join("negative.png");
+
+        run(&[
+            "apply".to_owned(),
+            "--op".to_owned(),
+            "negative".to_owned(),
+            "--in".to_owned(),
+            input.to_str().unwrap().to_owned(),
+            "--out".to_owned(),
+            output.to_str().unwrap().to_owned(),
+        ])?;
+
+        let expected = image::open(&input)?.negative().to_rgb8();
+        assert_eq!(image::open(&output)?.to_rgb8(), expected);
+
+        // resultados de 16 bits sao convertidos para o que o formato de saida aceita
+        let input = temp_dir.join("16bits.png");
+        let output = temp_dir.join("16bits.jpg");
+        image::open(image_folder_path.join("Gramado_22k.jpg"))?
+            .into_rgb16()
+            .save(&input)?;
+        run(&args(&format!(
+            "apply --op negative --in {} --out {}",
+            input.display(),
+            output.display()
+        )))?;
+        assert_eq!(image::open(&output)?.color(), image::ColorType::Rgb8);
+
+        std::fs::remove_dir_all(temp_dir)?;
+
+        Ok(())
+    }
+}
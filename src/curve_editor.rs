@@ -0,0 +1,290 @@
+use druid::{
+    kurbo::{BezPath, Circle, Line},
+    widget::{Button, Flex, Label},
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget, WidgetExt,
+};
+
+use crate::{imageops::Operation, imageops2::ToneCurve, select_operation, AppState};
+
+// Lado da area de edicao, com um pixel por tom
+pub const CURVE_SIZE: f64 = 256.0;
+
+// Distancia, em tons, para pegar um ponto com o mouse
+const GRAB_RADIUS: f32 = 8.0;
+
+#[derive(Debug, Data, Clone, Lens)]
+pub struct CurveEditor {
+    pub curve: ToneCurve,
+    // Ponto sendo arrastado. Fica no estado porque a interface e reconstruida a cada
+    // mudanca, e o widget novo precisa continuar o arrasto
+    pub dragging: Option<usize>,
+}
+
+impl Default for CurveEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CurveEditor {
+    pub fn new() -> Self {
+        Self {
+            curve: ToneCurve::identity(),
+            dragging: None,
+        }
+    }
+
+    // Ponto mais proximo de (x, y) dentro do raio de captura
+    pub fn point_near(&self, x: f32, y: f32) -> Option<usize> {
+        self.curve
+            .points()
+            .iter()
+            .enumerate()
+            .map(|(idx, (px, py))| (idx, (px - x).hypot(py - y)))
+            .filter(|(_, distance)| *distance <= GRAB_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx)
+    }
+
+    // Adiciona um ponto, substituindo o que tiver a mesma entrada, e devolve seu indice
+    pub fn add_point(&mut self, x: f32, y: f32) -> usize {
+        let x = x.round().clamp(0.0, 255.0);
+        let mut points = self.curve.points().to_vec();
+        points.push((x, y));
+        self.curve = ToneCurve::new(points);
+
+        self.curve
+            .points()
+            .iter()
+            .position(|(px, _)| *px == x)
+            .unwrap()
+    }
+
+    // Move o ponto sem passar dos vizinhos, para que a ordem dos pontos nao mude
+    pub fn move_point(&mut self, idx: usize, x: f32, y: f32) {
+        let mut points = self.curve.points().to_vec();
+        if idx >= points.len() {
+            return;
+        }
+
+        let min = if idx == 0 {
+            0.0
+        } else {
+            points[idx - 1].0 + 1.0
+        };
+        let max = points.get(idx + 1).map_or(255.0, |next| next.0 - 1.0);
+        points[idx] = (x.round().clamp(min, max), y.clamp(0.0, 255.0));
+
+        self.curve = ToneCurve::new(points);
+    }
+
+    // A curva sempre fica com pelo menos dois pontos
+    pub fn remove_point(&mut self, idx: usize) {
+        let mut points = self.curve.points().to_vec();
+        if points.len() > 2 && idx < points.len() {
+            points.remove(idx);
+            self.curve = ToneCurve::new(points);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.curve = ToneCurve::identity();
+        self.dragging = None;
+    }
+}
+
+// Mostra o resultado da curva editada; se outra operacao estava selecionada, passa a curva
+fn apply_curve(data: &mut AppState) {
+    let op = Operation::ToneCurve(data.curve_editor.curve.clone());
+
+    if let Operation::ToneCurve(_) = data.selected_operation {
+        data.selected_operation = op;
+    } else {
+        select_operation(data, op);
+    }
+}
+
+// Area onde a curva e desenhada e editada com o mouse: clicar fora dos pontos cria um
+// ponto novo, arrastar move o ponto e o botao direito ou clique duplo remove
+struct CurveCanvas;
+
+impl CurveCanvas {
+    fn to_curve(size: Size, pos: Point) -> (f32, f32) {
+        let x = pos.x / size.width * 255.0;
+        let y = (1.0 - pos.y / size.height) * 255.0;
+
+        (x as f32, y as f32)
+    }
+
+    fn to_screen(size: Size, x: f32, y: f32) -> Point {
+        Point::new(
+            x as f64 / 255.0 * size.width,
+            (1.0 - y as f64 / 255.0) * size.height,
+        )
+    }
+}
+
+impl Widget<AppState> for CurveCanvas {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
+        let size = ctx.size();
+
+        match event {
+            Event::MouseDown(mouse) => {
+                let (x, y) = Self::to_curve(size, mouse.pos);
+                let editor = &mut data.curve_editor;
+
+                if mouse.button.is_right() || mouse.count == 2 {
+                    if let Some(idx) = editor.point_near(x, y) {
+                        editor.remove_point(idx);
+                    }
+                    editor.dragging = None;
+                } else {
+                    let idx = editor
+                        .point_near(x, y)
+                        .unwrap_or_else(|| editor.add_point(x, y));
+                    editor.dragging = Some(idx);
+                    ctx.set_active(true);
+                }
+
+                apply_curve(data);
+                ctx.request_paint();
+            }
+            Event::MouseMove(mouse) => {
+                if let Some(idx) = data.curve_editor.dragging {
+                    // o botao pode ter sido solto enquanto a interface era reconstruida
+                    if !mouse.buttons.has_left() {
+                        data.curve_editor.dragging = None;
+                        return;
+                    }
+
+                    let (x, y) = Self::to_curve(size, mouse.pos);
+                    data.curve_editor.move_point(idx, x, y);
+                    apply_curve(data);
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseUp(_) => {
+                data.curve_editor.dragging = None;
+                ctx.set_active(false);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &AppState, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        if !old_data.curve_editor.same(&data.curve_editor) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &AppState, _: &Env) -> Size {
+        bc.constrain(Size::new(CURVE_SIZE, CURVE_SIZE))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, _env: &Env) {
+        let size = ctx.size();
+        let rect = size.to_rect();
+        ctx.fill(rect, &Color::grey(0.15));
+
+        // grade a cada quarto da faixa e a diagonal da curva identidade
+        let grid = Color::grey(0.3);
+        for i in 1..4 {
+            let t = i as f64 / 4.0;
+            ctx.stroke(
+                Line::new((t * size.width, 0.0), (t * size.width, size.height)),
+                &grid,
+                1.0,
+            );
+            ctx.stroke(
+                Line::new((0.0, t * size.height), (size.width, t * size.height)),
+                &grid,
+                1.0,
+            );
+        }
+        ctx.stroke(Line::new((0.0, size.height), (size.width, 0.0)), &grid, 1.0);
+
+        let curve = &data.curve_editor.curve;
+        let mut path = BezPath::new();
+        for (idx, y) in curve.values(256).iter().enumerate() {
+            let point = Self::to_screen(size, idx as f32, y * 255.0);
+            if idx == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        ctx.stroke(path, &Color::WHITE, 2.0);
+
+        for (idx, (x, y)) in curve.points().iter().enumerate() {
+            let color = if data.curve_editor.dragging == Some(idx) {
+                Color::rgb8(255, 160, 0)
+            } else {
+                Color::WHITE
+            };
+            ctx.fill(Circle::new(Self::to_screen(size, *x, *y), 4.0), &color);
+        }
+    }
+}
+
+pub fn build_curve_editor() -> impl Widget<AppState> {
+    let actions = Flex::row()
+        .with_child(
+            Button::new("Aplicar Curva")
+                .on_click(|_ctx, data: &mut AppState, _env| apply_curve(data)),
+        )
+        .with_child(
+            Button::new("Resetar").on_click(|_ctx, data: &mut AppState, _env| {
+                data.curve_editor.reset();
+                apply_curve(data);
+            }),
+        );
+
+    Flex::column()
+        .with_child(Label::new("Curva de tons"))
+        .with_child(
+            CurveCanvas
+                .fix_size(CURVE_SIZE, CURVE_SIZE)
+                .border(Color::grey(0.6), 2.0),
+        )
+        .with_child(actions)
+        .padding(10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve_editor::CurveEditor;
+
+    #[test]
+    fn test_editor_points() {
+        let mut editor = CurveEditor::new();
+        assert_eq!(editor.curve.points(), &[(0.0, 0.0), (255.0, 255.0)]);
+
+        // pontos sao criados na ordem das entradas e pegos pela proximidade
+        let idx = editor.add_point(128.3, 180.0);
+        assert_eq!(idx, 1);
+        assert_eq!(editor.point_near(130.0, 175.0), Some(1));
+        assert_eq!(editor.point_near(64.0, 64.0), None);
+        assert_eq!(editor.add_point(128.0, 200.0), 1);
+        assert_eq!(editor.curve.points().len(), 3);
+        assert_eq!(editor.curve.lut()[128], 200);
+
+        // arrastar nao passa dos vizinhos nem sai da faixa
+        editor.move_point(1, 300.0, -20.0);
+        assert_eq!(editor.curve.points()[1], (254.0, 0.0));
+        editor.move_point(0, 100.0, 30.0);
+        assert_eq!(editor.curve.points()[0], (100.0, 30.0));
+
+        editor.remove_point(1);
+        assert_eq!(editor.curve.points().len(), 2);
+        editor.remove_point(0);
+        assert_eq!(editor.curve.points().len(), 2);
+
+        editor.dragging = Some(1);
+        editor.reset();
+        assert_eq!(editor.curve.lut()[77], 77);
+        assert_eq!(editor.dragging, None);
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    borrow::Cow,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use druid::{Data, FileSpec};
+use image::{codecs::jpeg::JpegEncoder, ColorType, DynamicImage, ImageFormat};
+
+use crate::precision::{convert_to, is_float, to_8bit};
+use crate::recipe::{Recipe, RecipeError};
+
+#[derive(Debug, Data, Clone, Copy, PartialEq)]
+pub enum SaveFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tiff,
+    WebP,
+    OpenExr,
+}
+
+impl SaveFormat {
+    pub const ALL: [SaveFormat; 6] = [
+        SaveFormat::Png,
+        SaveFormat::Jpeg,
+        SaveFormat::Bmp,
+        SaveFormat::Tiff,
+        SaveFormat::WebP,
+        SaveFormat::OpenExr,
+    ];
+
+    pub fn image_format(&self) -> ImageFormat {
+        match self {
+            SaveFormat::Png => ImageFormat::Png,
+            SaveFormat::Jpeg => ImageFormat::Jpeg,
+            SaveFormat::Bmp => ImageFormat::Bmp,
+            SaveFormat::Tiff => ImageFormat::Tiff,
+            SaveFormat::WebP => ImageFormat::WebP,
+            SaveFormat::OpenExr => ImageFormat::OpenExr,
+        }
+    }
+
+    // Formatos que a versao atual do crate image consegue escrever
+    pub fn supported() -> Vec<SaveFormat> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|format| format.image_format().can_write())
+            .collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaveFormat::Png => "PNG",
+            SaveFormat::Jpeg => "JPEG",
+            SaveFormat::Bmp => "BMP",
+            SaveFormat::Tiff => "TIFF",
+            SaveFormat::WebP => "WebP",
+            SaveFormat::OpenExr => "EXR",
+        }
+    }
+
+    pub fn file_spec(&self) -> FileSpec {
+        match self {
+            SaveFormat::Png => FileSpec::new("PNG", &["png"]),
+            SaveFormat::Jpeg => FileSpec::new("JPEG", &["jpg", "jpeg"]),
+            SaveFormat::Bmp => FileSpec::new("BMP", &["bmp"]),
+            SaveFormat::Tiff => FileSpec::new("TIFF", &["tif", "tiff"]),
+            SaveFormat::WebP => FileSpec::new("WebP", &["webp"]),
+            SaveFormat::OpenExr => FileSpec::new("OpenEXR", &["exr"]),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.image_format().extensions_str()[0]
+    }
+
+    // Formato indicado pela extensao do caminho
+    pub fn from_path(path: &Path) -> Option<SaveFormat> {
+        let format = ImageFormat::from_path(path).ok()?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|known| known.image_format() == format)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions {
+    pub format: SaveFormat,
+    // Qualidade do JPEG, de 1 a 100
+    pub quality: u8,
+    // Receita gravada num bloco tEXt do PNG, ou num .json ao lado da imagem nos outros formatos
+    pub recipe: Option<Recipe>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Unsupported(SaveFormat),
+    UnknownFormat(PathBuf),
+    Io(io::Error),
+    Image(image::ImageError),
+    Recipe(RecipeError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Unsupported(format) => {
+                write!(
+                    f,
+                    "o formato {} nao e suportado para escrita",
+                    format.name()
+                )
+            }
+            SaveError::UnknownFormat(path) => {
+                write!(f, "formato de arquivo desconhecido: {}", path.display())
+            }
+            SaveError::Io(err) => write!(f, "erro ao salvar: {}", err),
+            SaveError::Image(err) => write!(f, "erro ao codificar a imagem: {}", err),
+            SaveError::Recipe(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for SaveError {
+    fn from(err: image::ImageError) -> Self {
+        SaveError::Image(err)
+    }
+}
+
+impl From<RecipeError> for SaveError {
+    fn from(err: RecipeError) -> Self {
+        SaveError::Recipe(err)
+    }
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const RECIPE_KEYWORD: &str = "fpi-recipe";
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+// Insere um bloco tEXt logo apos o IHDR do PNG
+fn insert_png_text(png: &[u8], keyword: &str, text: &str) -> Vec<u8> {
+    // assinatura (8) + tamanho (4) + tipo (4) + dados do IHDR (13) + crc (4)
+    let ihdr_end = 8 + 4 + 4 + 13 + 4;
+
+    let mut data = keyword.as_bytes().to_vec();
+    data.push(0);
+    data.extend_from_slice(text.as_bytes());
+
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    let mut typed = b"tEXt".to_vec();
+    typed.extend_from_slice(&data);
+    chunk.extend_from_slice(&typed);
+    chunk.extend_from_slice(&crc32(&typed).to_be_bytes());
+
+    let mut out = png[..ihdr_end].to_vec();
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&png[ihdr_end..]);
+    out
+}
+
+// Texto do bloco tEXt com a palavra-chave, se o arquivo for um PNG que o tenha
+pub fn read_png_text(png: &[u8], keyword: &str) -> Option<String> {
+    if !png.starts_with(&PNG_SIGNATURE) {
+        return None;
+    }
+
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes([png[pos], png[pos + 1], png[pos + 2], png[pos + 3]]) as usize;
+        let kind = &png[pos + 4..pos + 8];
+        let data = png.get(pos + 8..pos + 8 + len)?;
+
+        if kind == b"tEXt" {
+            if let Some(split) = data.iter().position(|b| *b == 0) {
+                if &data[..split] == keyword.as_bytes() {
+                    return String::from_utf8(data[split + 1..].to_vec()).ok();
+                }
+            }
+        }
+
+        pos += 12 + len;
+    }
+
+    None
+}
+
+pub fn read_embedded_recipe(png: &[u8]) -> Option<String> {
+    read_png_text(png, RECIPE_KEYWORD)
+}
+
+// Troca a extensao do caminho se ela nao for uma das do formato
+pub fn with_extension(path: &Path, format: SaveFormat) -> PathBuf {
+    let matches = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            format
+                .image_format()
+                .extensions_str()
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false);
+
+    if matches {
+        path.to_path_buf()
+    } else {
+        path.with_extension(format.extension())
+    }
+}
+
+// Converte para o tipo de pixel mais proximo que o codificador do formato aceita:
+// PNG e TIFF guardam 16 bits, EXR guarda ponto flutuante e os demais apenas 8 bits
+fn encodable(image: &DynamicImage, format: SaveFormat) -> Cow<'_, DynamicImage> {
+    let alpha = image.color().has_alpha();
+    let color = match (format, image.color()) {
+        (SaveFormat::OpenExr, ColorType::Rgb32F | ColorType::Rgba32F) => None,
+        (SaveFormat::OpenExr, _) if alpha => Some(ColorType::Rgba32F),
+        (SaveFormat::OpenExr, _) => Some(ColorType::Rgb32F),
+        (SaveFormat::Png | SaveFormat::Tiff, _) if is_float(image) => Some(if alpha {
+            ColorType::Rgba16
+        } else {
+            ColorType::Rgb16
+        }),
+        (SaveFormat::Tiff, ColorType::La8) => Some(ColorType::Rgba8),
+        (SaveFormat::Tiff, ColorType::La16) => Some(ColorType::Rgba16),
+        (SaveFormat::Png | SaveFormat::Tiff, _) => None,
+        (SaveFormat::Jpeg | SaveFormat::Bmp | SaveFormat::WebP, _) => {
+            return Cow::Owned(to_8bit(image));
+        }
+    };
+
+    match color {
+        Some(color) => Cow::Owned(convert_to(image, color)),
+        None => Cow::Borrowed(image),
+    }
+}
+
+// Salva a imagem e devolve o caminho final, com a extensao do formato escolhido
+pub fn save_image(
+    image: &DynamicImage,
+    path: &Path,
+    options: &SaveOptions,
+) -> Result<PathBuf, SaveError> {
+    if !options.format.image_format().can_write() {
+        return Err(SaveError::Unsupported(options.format));
+    }
+
+    let path = with_extension(path, options.format);
+    let image = encodable(image, options.format);
+    let json = match &options.recipe {
+        Some(recipe) => Some(recipe.to_json()?),
+        None => None,
+    };
+
+    match options.format {
+        SaveFormat::Png => {
+            let mut bytes = io::Cursor::new(vec![]);
+            image.write_to(&mut bytes, ImageFormat::Png)?;
+            let mut bytes = bytes.into_inner();
+            if let Some(json) = &json {
+                bytes = insert_png_text(&bytes, RECIPE_KEYWORD, json);
+            }
+            fs::write(&path, bytes)?;
+        }
+        SaveFormat::Jpeg => {
+            // JPEG nao tem canal alfa
+            let rgb = image.to_rgb8();
+            let mut bytes = vec![];
+            JpegEncoder::new_with_quality(&mut bytes, options.quality.clamp(1, 100))
+                .encode_image(&rgb)?;
+            fs::write(&path, bytes)?;
+        }
+        // o codificador de TIFF precisa de um arquivo com Seek
+        format => image.save_with_format(&path, format.image_format())?,
+    }
+
+    // so o PNG guarda a receita no proprio arquivo
+    if options.format != SaveFormat::Png {
+        if let Some(json) = json {
+            fs::write(path.with_extension("json"), json)?;
+        }
+    }
+
+    Ok(path)
+}
+
+// Salva sem receita, no formato indicado pela extensao do caminho
+pub fn save_to_path(image: &DynamicImage, path: &Path) -> Result<PathBuf, SaveError> {
+    let format =
+        SaveFormat::from_path(path).ok_or_else(|| SaveError::UnknownFormat(path.to_path_buf()))?;
+    let options = SaveOptions {
+        format,
+        quality: 90,
+        recipe: None,
+    };
+
+    save_image(image, path, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, path::Path};
+
+    use image::GenericImageView;
+
+    use crate::export::{crc32, read_embedded_recipe, save_image, SaveFormat, SaveOptions};
+    use crate::history::Step;
+    use crate::imageops::Operation;
+    use crate::pipeline::Pipeline;
+    use crate::recipe::Recipe;
+    use crate::AppState;
+
+    #[test]
+    fn test_save_formats() -> Result<(), Box<dyn Error>> {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert!(!SaveFormat::supported().is_empty());
+
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+        let dir = std::env::temp_dir().join(format!("fpi-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let mut pipeline = Pipeline::new();
+        pipeline.push(Step::from_state(Operation::Negative, &AppState::new()));
+        let recipe = Recipe::new(pipeline);
+
+        // o PNG continua valido e guarda a receita
+        let options = SaveOptions {
+            format: SaveFormat::Png,
+            quality: 90,
+            recipe: Some(recipe.clone()),
+        };
+        let path = save_image(&img, &dir.join("resultado.jpg"), &options)?;
+        assert_eq!(path, dir.join("resultado.png"));
+        assert_eq!(image::open(&path)?.to_rgb8(), img.to_rgb8());
+        let embedded = read_embedded_recipe(&std::fs::read(&path)?).unwrap();
+        assert_eq!(Recipe::from_json(&embedded)?, recipe);
+        assert_eq!(Recipe::load(&path)?, recipe);
+
+        // qualidade menor gera arquivos menores
+        let jpeg = |quality| -> Result<u64, Box<dyn Error>> {
+            let options = SaveOptions {
+                format: SaveFormat::Jpeg,
+                quality,
+                recipe: None,
+            };
+            let path = save_image(&img, &dir.join(format!("q{}", quality)), &options)?;
+            assert_eq!(path.extension().unwrap(), "jpg");
+            Ok(std::fs::metadata(path)?.len())
+        };
+        assert!(jpeg(10)? < jpeg(95)?);
+
+        // nos outros formatos a receita vai num .json ao lado
+        let options = SaveOptions {
+            format: SaveFormat::Bmp,
+            quality: 90,
+            recipe: Some(recipe.clone()),
+        };
+        let path = save_image(&img, &dir.join("resultado.bmp"), &options)?;
+        assert_eq!(image::open(&path)?.to_rgb8(), img.to_rgb8());
+        assert_eq!(Recipe::load(path.with_extension("json"))?, recipe);
+
+        for format in SaveFormat::supported() {
+            let options = SaveOptions {
+                format,
+                quality: 90,
+                recipe: None,
+            };
+            let path = save_image(&img, &dir.join("formato"), &options)?;
+            assert_eq!(image::open(&path)?.dimensions(), img.dimensions());
+        }
+
+        // 16 bits sao mantidos em PNG e TIFF e reduzidos nos formatos de 8 bits
+        let deep = image::DynamicImage::ImageRgb16(img.to_rgb16());
+        for format in [SaveFormat::Png, SaveFormat::Tiff].iter() {
+            let options = SaveOptions {
+                format: *format,
+                quality: 90,
+                recipe: None,
+            };
+            let path = save_image(&deep, &dir.join("16bits"), &options)?;
+            assert_eq!(image::open(&path)?, deep);
+        }
+        let options = SaveOptions {
+            format: SaveFormat::Bmp,
+            quality: 90,
+            recipe: None,
+        };
+        let path = save_image(&deep, &dir.join("16bits"), &options)?;
+        assert_eq!(image::open(&path)?.to_rgb8(), img.to_rgb8());
+
+        // HDR mantem valores acima de 1.0 em EXR
+        let hdr = image::DynamicImage::ImageRgb32F(image::ImageBuffer::from_pixel(
+            4,
+            4,
+            image::Rgb([2.5f32, 0.5, 0.0]),
+        ));
+        let options = SaveOptions {
+            format: SaveFormat::OpenExr,
+            quality: 90,
+            recipe: None,
+        };
+        let path = save_image(&hdr, &dir.join("hdr"), &options)?;
+        assert_eq!(image::open(&path)?.to_rgb32f(), hdr.to_rgb32f());
+
+        // erros sao devolvidos em vez de causar panico
+        let missing = dir.join("nao-existe").join("a.png");
+        assert!(save_image(&img, &missing, &options).is_err());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
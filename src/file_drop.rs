@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use druid::{ExtEventSink, Selector};
+
+// Arquivo ou pasta solto sobre a janela
+pub const FILE_DROPPED: Selector<PathBuf> = Selector::new("fpi.file-dropped");
+
+// O druid 0.7 nao repassa arquivos arrastados para a janela, entao no Linux o evento e
+// tratado direto nas janelas do GTK e chega ao druid como FILE_DROPPED
+#[cfg(target_os = "linux")]
+pub fn accept_drops(sink: ExtEventSink) {
+    use druid::Target;
+    use gtk::prelude::*;
+
+    let targets = [gtk::TargetEntry::new(
+        "text/uri-list",
+        gtk::TargetFlags::empty(),
+        0,
+    )];
+
+    for window in gtk::Window::list_toplevels() {
+        window.drag_dest_set(gtk::DestDefaults::ALL, &targets, gdk::DragAction::COPY);
+
+        let sink = sink.clone();
+        window.connect_drag_data_received(move |_, _, _, _, data, _, _| {
+            // com varios arquivos soltos de uma vez, abre o primeiro
+            if let Some(path) = uri_paths(&data.get_uris()).into_iter().next() {
+                let _ = sink.submit_command(FILE_DROPPED, path, Target::Auto);
+            }
+        });
+    }
+}
+
+// Nas outras plataformas a janela ainda nao aceita arquivos soltos
+#[cfg(not(target_os = "linux"))]
+pub fn accept_drops(_sink: ExtEventSink) {}
+
+// Caminhos locais de uma lista de URIs, ignorando as que nao sao arquivos
+#[cfg(target_os = "linux")]
+fn uri_paths<S: AsRef<str>>(uris: &[S]) -> Vec<PathBuf> {
+    uris.iter()
+        .filter_map(|uri| glib::filename_from_uri(uri.as_ref()).ok())
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::file_drop::uri_paths;
+
+    #[test]
+    fn test_uri_paths() {
+        let paths = uri_paths(&[
+            "file:///tmp/minha%20imagem.png",
+            "https://example.com/imagem.png",
+            "file:///tmp/pasta",
+        ]);
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/tmp/minha imagem.png"),
+                PathBuf::from("/tmp/pasta")
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use druid::{Data, Lens};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    apply_operation,
+    imageops::Operation,
+    imageops2::{BorderMode, ConvolutionColor, GradientOperator, ResizeFilter},
+    AppState,
+};
+
+// Operacao aplicada junto com os parametros que estavam ativos no momento.
+// Campos ausentes em receitas antigas assumem os valores padrao do AppState
+#[derive(Debug, Data, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    pub operation: Operation,
+    pub param1: f64,
+    pub param2: f64,
+    pub param3: f64,
+    pub angle: f64,
+    pub expand_canvas: bool,
+    pub resize_filter: ResizeFilter,
+    pub resize_width: u32,
+    pub resize_height: u32,
+    pub border_mode: BorderMode,
+    pub convolution_color: ConvolutionColor,
+    pub gradient_operator: GradientOperator,
+    pub target_image: Option<String>,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self::from_state(Operation::None, &AppState::new())
+    }
+}
+
+impl Step {
+    pub fn from_state(operation: Operation, state: &AppState) -> Self {
+        Self {
+            operation,
+            param1: state.param1,
+            param2: state.param2,
+            param3: state.param3,
+            angle: state.angle,
+            expand_canvas: state.expand_canvas,
+            resize_filter: state.resize_filter,
+            resize_width: state.resize_width,
+            resize_height: state.resize_height,
+            border_mode: state.border_mode,
+            convolution_color: state.convolution_color,
+            gradient_operator: state.gradient_operator,
+            target_image: state.target_image.clone(),
+        }
+    }
+
+    // Copia os parametros do passo para o estado
+    pub fn restore(&self, state: &mut AppState) {
+        state.param1 = self.param1;
+        state.param2 = self.param2;
+        state.param3 = self.param3;
+        state.angle = self.angle;
+        state.expand_canvas = self.expand_canvas;
+        state.resize_filter = self.resize_filter;
+        state.resize_width = self.resize_width;
+        state.resize_height = self.resize_height;
+        state.border_mode = self.border_mode;
+        state.convolution_color = self.convolution_color;
+        state.gradient_operator = self.gradient_operator;
+        state.target_image = self.target_image.clone();
+    }
+
+    pub fn apply(&self, image: &DynamicImage, state: &AppState) -> DynamicImage {
+        let mut step_state = state.clone();
+        self.restore(&mut step_state);
+        step_state.selected_operation = self.operation.clone();
+        step_state.last_operation = Operation::None;
+
+        apply_operation(image, &self.operation, &step_state)
+    }
+}
+
+#[derive(Debug, Data, Clone, Default, Lens)]
+pub struct History {
+    steps: Arc<Vec<Step>>,
+    // Quantidade de passos aplicados, os seguintes podem ser refeitos
+    cursor: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adiciona um passo, descartando os que poderiam ser refeitos
+    pub fn push(&mut self, step: Step) {
+        let steps = Arc::make_mut(&mut self.steps);
+        steps.truncate(self.cursor);
+        steps.push(step);
+        self.cursor = steps.len();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if self.can_undo() {
+            self.cursor -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if self.can_redo() {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.steps.len()
+    }
+
+    pub fn applied(&self) -> &[Step] {
+        &self.steps[..self.cursor]
+    }
+
+    pub fn clear(&mut self) {
+        self.steps = Arc::new(vec![]);
+        self.cursor = 0;
+    }
+
+    // Aplica cada passo sobre o resultado do anterior
+    pub fn apply(&self, image: &DynamicImage, state: &AppState) -> DynamicImage {
+        self.apply_until(image, state, &|| false).unwrap()
+    }
+
+    // Como `apply`, mas desiste antes de cada passo se `cancelled` devolver true
+    pub fn apply_until(
+        &self,
+        image: &DynamicImage,
+        state: &AppState,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Option<DynamicImage> {
+        let mut image = image.clone();
+        for step in self.applied() {
+            if cancelled() {
+                return None;
+            }
+            image = step.apply(&image, state);
+        }
+
+        Some(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, path::Path};
+
+    use crate::history::{History, Step};
+    use crate::imageops::{ImageExt, Operation};
+    use crate::imageops2::ImageExt2;
+    use crate::AppState;
+
+    #[test]
+    fn test_history_undo_redo() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        let mut state = AppState::new();
+        let mut history = History::new();
+        assert!(!history.can_undo());
+
+        history.push(Step::from_state(Operation::FlipH, &state));
+        state.param1 = 32.0;
+        history.push(Step::from_state(Operation::AdjustBrightness, &state));
+
+        // cada passo usa os parametros de quando foi aplicado
+        state.param1 = 200.0;
+        let expected = img.flip_h().adjust_brightness(32);
+        assert_eq!(history.apply(&img, &state), expected);
+
+        assert!(history.undo());
+        assert_eq!(history.apply(&img, &state), img.flip_h());
+        assert!(history.can_redo());
+
+        assert!(history.redo());
+        assert_eq!(history.apply(&img, &state), expected);
+        assert!(!history.redo());
+
+        // aplicar um novo passo depois de desfazer descarta o refazer
+        history.undo();
+        history.push(Step::from_state(Operation::Negative, &state));
+        assert!(!history.can_redo());
+        assert_eq!(history.applied().len(), 2);
+        assert_eq!(history.apply(&img, &state), img.flip_h().negative());
+
+        history.clear();
+        assert_eq!(history.apply(&img, &state), img);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,258 @@
 use druid::{piet::ImageFormat, widget::Image, Data, ImageBuf};
 use image::{
-    DynamicImage, EncodableLayout, GenericImage, GenericImageView, GrayImage, Luma, RgbImage, Rgba,
+    DynamicImage, EncodableLayout, GenericImage, GenericImageView, GrayAlphaImage, GrayImage,
+    ImageBuffer, Luma, LumaA, Pixel, RgbImage, Rgba,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::imageops2::Kernel;
+use crate::imageops2::{EqualizeMode, GradientOutput, Kernel, Normalization, ToneCurve};
+use crate::pipeline::Pipeline;
+use crate::precision::{
+    float_luma, float_pixels, from_float_pixels, grayscale_color, is_high_precision, to_8bit,
+};
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq)]
+pub enum RotateCanvas {
+    // Aumenta a imagem para caber toda a imagem rotacionada
+    Expand,
+    // Mantem as dimensoes originais, cortando os cantos
+    Crop,
+}
+
+// Kernel cubico de Keys com a = -0.5
+pub(crate) fn cubic_weight(t: f32) -> f32 {
+    let a = -0.5;
+    let t = t.abs();
+
+    if t <= 1.0 {
+        (a + 2.0) * t.powi(3) - (a + 3.0) * t.powi(2) + 1.0
+    } else if t < 2.0 {
+        a * t.powi(3) - 5.0 * a * t.powi(2) + 8.0 * a * t - 4.0 * a
+    } else {
+        0.0
+    }
+}
+
+// Imagem vazia com as dimensoes pedidas e canal alfa apenas se a original tiver
+pub(crate) fn new_like(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if image.color().has_alpha() {
+        DynamicImage::new_rgba8(width, height)
+    } else {
+        DynamicImage::new_rgb8(width, height)
+    }
+}
+
+// Media ponderada com alfa pre-multiplicado, para que a cor de pixels transparentes
+// nao vaze para os vizinhos
+pub(crate) fn blend(samples: &[(Rgba<u8>, f32)]) -> Rgba<u8> {
+    let mut acc = [0.0f32; 4];
+    for (pixel, weight) in samples {
+        let alpha = pixel[3] as f32 * weight;
+        for (channel, value) in acc.iter_mut().take(3).enumerate() {
+            *value += pixel[channel] as f32 * alpha;
+        }
+        acc[3] += alpha;
+    }
+
+    let alpha = acc[3];
+    if alpha <= 0.0 {
+        return Rgba::from([0, 0, 0, 0]);
+    }
+
+    let color = |value: f32| (value / alpha).round().clamp(0.0, 255.0) as u8;
+
+    Rgba::from([
+        color(acc[0]),
+        color(acc[1]),
+        color(acc[2]),
+        alpha.round().clamp(0.0, 255.0) as u8,
+    ])
+}
+
+// Mesma media de `blend` com os canais em ponto flutuante, para 16 bits e HDR. Os tons
+// podem passar de 1, entao so o alfa e limitado
+fn blend_float(samples: &[([f32; 4], f32)]) -> [f32; 4] {
+    let mut acc = [0.0f32; 4];
+    for (pixel, weight) in samples {
+        let alpha = pixel[3] * weight;
+        for (channel, value) in acc.iter_mut().take(3).enumerate() {
+            *value += pixel[channel] * alpha;
+        }
+        acc[3] += alpha;
+    }
+
+    let alpha = acc[3];
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+
+    let color = |value: f32| (value / alpha).max(0.0);
+    [
+        color(acc[0]),
+        color(acc[1]),
+        color(acc[2]),
+        alpha.clamp(0.0, 1.0),
+    ]
+}
+
+// Vizinhos de (x, y) e seus pesos na interpolacao; o vizinho mais proximo tem peso 1
+fn interpolation_weights(x: f32, y: f32, interpolation: Interpolation) -> Vec<(i64, i64, f32)> {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    match interpolation {
+        Interpolation::Nearest => vec![(x.round() as i64, y.round() as i64, 1.0)],
+        Interpolation::Bilinear => vec![
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x0 + 1, y0, fx * (1.0 - fy)),
+            (x0, y0 + 1, (1.0 - fx) * fy),
+            (x0 + 1, y0 + 1, fx * fy),
+        ],
+        Interpolation::Bicubic => {
+            let mut weights = Vec::with_capacity(16);
+            for j in -1..=2 {
+                for i in -1..=2 {
+                    let weight = cubic_weight(i as f32 - fx) * cubic_weight(j as f32 - fy);
+                    weights.push((x0 + i, y0 + j, weight));
+                }
+            }
+
+            weights
+        }
+    }
+}
+
+// Amostra os pixels em ponto flutuante de uma imagem w x h, repetindo a borda
+fn sample_float(
+    pixels: &[[f32; 4]],
+    (w, h): (u32, u32),
+    x: f32,
+    y: f32,
+    interpolation: Interpolation,
+) -> [f32; 4] {
+    let get = |x: i64, y: i64| {
+        let x = x.clamp(0, w as i64 - 1) as usize;
+        let y = y.clamp(0, h as i64 - 1) as usize;
+        pixels[y * w as usize + x]
+    };
+
+    let weights = interpolation_weights(x, y, interpolation);
+    if interpolation == Interpolation::Nearest {
+        let (x, y, _) = weights[0];
+        return get(x, y);
+    }
+
+    let samples: Vec<_> = weights
+        .iter()
+        .map(|(x, y, weight)| (get(*x, *y), *weight))
+        .collect();
+    blend_float(&samples)
+}
+
+fn luma(pixel: Rgba<u8>) -> u8 {
+    ((0.299 * pixel[0] as f64) as u64
+        + (0.587 * pixel[1] as f64) as u64
+        + (0.114 * pixel[2] as f64) as u64) as u8
+}
+
+// Aplica `$body` ao buffer de qualquer tipo de pixel, devolvendo o mesmo tipo de imagem
+macro_rules! map_buffer {
+    ($image:expr, $buffer:ident => $body:expr) => {
+        match $image {
+            DynamicImage::ImageLuma8($buffer) => DynamicImage::ImageLuma8($body),
+            DynamicImage::ImageLumaA8($buffer) => DynamicImage::ImageLumaA8($body),
+            DynamicImage::ImageRgb8($buffer) => DynamicImage::ImageRgb8($body),
+            DynamicImage::ImageRgba8($buffer) => DynamicImage::ImageRgba8($body),
+            DynamicImage::ImageLuma16($buffer) => DynamicImage::ImageLuma16($body),
+            DynamicImage::ImageLumaA16($buffer) => DynamicImage::ImageLumaA16($body),
+            DynamicImage::ImageRgb16($buffer) => DynamicImage::ImageRgb16($body),
+            DynamicImage::ImageRgba16($buffer) => DynamicImage::ImageRgba16($body),
+            DynamicImage::ImageRgb32F($buffer) => DynamicImage::ImageRgb32F($body),
+            DynamicImage::ImageRgba32F($buffer) => DynamicImage::ImageRgba32F($body),
+            image => {
+                let $buffer = &image.to_rgba32f();
+                DynamicImage::ImageRgba32F($body)
+            }
+        }
+    };
+}
+
+type Buffer<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+// Gira o buffer 90 graus no sentido horario
+fn rotate_buffer_cw<P: Pixel>(buffer: &Buffer<P>) -> Buffer<P> {
+    let (w, h) = buffer.dimensions();
+    let mut rotated = ImageBuffer::new(h, w);
+
+    for (x, y, pixel) in buffer.enumerate_pixels() {
+        rotated.put_pixel(h - 1 - y, x, *pixel);
+    }
+
+    rotated
+}
+
+// Gira o buffer 90 graus no sentido anti-horario
+fn rotate_buffer_ccw<P: Pixel>(buffer: &Buffer<P>) -> Buffer<P> {
+    let (w, h) = buffer.dimensions();
+    let mut rotated = ImageBuffer::new(h, w);
+
+    for (x, y, pixel) in buffer.enumerate_pixels() {
+        rotated.put_pixel(y, w - 1 - x, *pixel);
+    }
+
+    rotated
+}
+
+// Troca as linhas de cima com as de baixo; com altura impar a linha do meio fica no lugar
+fn flip_rows<P: Pixel>(buffer: &Buffer<P>) -> Buffer<P> {
+    let (w, h) = buffer.dimensions();
+    let stride = w as usize * P::CHANNEL_COUNT as usize;
+    let mut raw = buffer.as_raw().clone();
+
+    // a metade de baixo comeca na linha h / 2, que e a do meio quando h e impar
+    let (upper, lower) = raw.split_at_mut(stride * (h as usize / 2));
+    let lower_start = h as usize / 2;
+
+    for row in 0..(h as usize / 2) {
+        let mirror = h as usize - 1 - row - lower_start;
+        let row_upper = &mut upper[row * stride..(row + 1) * stride];
+        let row_lower = &mut lower[mirror * stride..(mirror + 1) * stride];
+
+        row_lower.swap_with_slice(row_upper);
+    }
+
+    ImageBuffer::from_raw(w, h, raw).unwrap()
+}
+
+// Inverte a ordem dos pixels de cada linha, mantendo os canais de cada pixel juntos
+fn flip_columns<P: Pixel>(buffer: &Buffer<P>) -> Buffer<P> {
+    let (w, h) = buffer.dimensions();
+    let channels = P::CHANNEL_COUNT as usize;
+    let mut raw = buffer.as_raw().clone();
+
+    if w > 1 {
+        for row in raw.chunks_exact_mut(w as usize * channels) {
+            for x in 0..(w as usize / 2) {
+                let mirror = w as usize - 1 - x;
+                let (left, right) = row.split_at_mut(mirror * channels);
+
+                left[x * channels..(x + 1) * channels].swap_with_slice(&mut right[..channels]);
+            }
+        }
+    }
+
+    ImageBuffer::from_raw(w, h, raw).unwrap()
+}
 
 pub trait ImageExt {
     fn flip_v(&self) -> DynamicImage;
@@ -12,51 +261,57 @@ pub trait ImageExt {
     fn to_grayscale_rgb(&self) -> DynamicImage;
     fn get_dimensions(&self) -> (u32, u32);
     fn quantize_grayscale(&self, qty: u8) -> DynamicImage;
+    fn sample(&self, x: f32, y: f32, interpolation: Interpolation) -> Rgba<u8>;
+    fn rotate_90_cw(&self) -> DynamicImage;
+    fn rotate_90_ccw(&self) -> DynamicImage;
+    fn rotate(
+        &self,
+        angle: f32,
+        interpolation: Interpolation,
+        canvas: RotateCanvas,
+    ) -> DynamicImage;
 }
 
 impl ImageExt for DynamicImage {
     fn flip_v(&self) -> DynamicImage {
-        let mut raw = self.clone().into_bytes();
-
-        let (w, h) = self.dimensions();
-        let stride = (w * 3) as usize;
-        let len = (stride * h as usize / 2) as usize;
-        let slice_h = h as usize / 2;
+        map_buffer!(self, buffer => flip_rows(buffer))
+    }
 
-        let (first, second) = raw.split_at_mut(len);
+    fn flip_h(&self) -> DynamicImage {
+        map_buffer!(self, buffer => flip_columns(buffer))
+    }
 
-        for idx in 0..(h as usize / 2) {
-            let mut row_upper = &mut first[idx * stride..(idx + 1) * stride];
-            let row_lower = &mut second[(slice_h - idx - 1) * stride..(slice_h - idx) * stride];
+    fn to_grayscale(&self) -> DynamicImage {
+        let (width, height) = self.get_dimensions();
 
-            row_lower.swap_with_slice(&mut row_upper);
+        // 16 bits e HDR sao convertidos em ponto flutuante, sem perder precisao
+        if is_high_precision(self) {
+            let pixels = float_pixels(self)
+                .iter()
+                .map(|pixel| {
+                    let l = float_luma(pixel);
+                    [l, l, l, pixel[3]]
+                })
+                .collect();
+
+            return from_float_pixels(width, height, pixels, grayscale_color(self));
         }
 
-        DynamicImage::ImageRgb8(RgbImage::from_raw(w, h, raw).unwrap())
-    }
+        // imagens com transparencia mantem o alfa num LumaA
+        if self.color().has_alpha() {
+            let mut new_img = GrayAlphaImage::new(width, height);
 
-    fn flip_h(&self) -> DynamicImage {
-        let (w, h) = self.dimensions();
-        let mut new_img = DynamicImage::new_rgb8(w, h);
+            for (x, y, pixel) in self.pixels() {
+                new_img.put_pixel(x, y, LumaA([luma(pixel), pixel[3]]));
+            }
 
-        for (x, y, pixel) in self.pixels() {
-            new_img.put_pixel((w - 1 - x) as u32, y, pixel);
+            return DynamicImage::ImageLumaA8(new_img);
         }
 
-        new_img
-    }
-
-    fn to_grayscale(&self) -> DynamicImage {
-        let (width, height) = self.get_dimensions();
-
         let mut new_img: GrayImage = GrayImage::new(width, height);
 
         for (x, y, pixel) in self.pixels() {
-            let new_l = (0.299 * pixel[0] as f64) as u64
-                + (0.587 * pixel[1] as f64) as u64
-                + (0.114 * pixel[2] as f64) as u64;
-
-            new_img.put_pixel(x, y, Luma([new_l as u8]));
+            new_img.put_pixel(x, y, Luma([luma(pixel)]));
         }
 
         DynamicImage::ImageLuma8(new_img)
@@ -65,31 +320,35 @@ impl ImageExt for DynamicImage {
     fn to_grayscale_rgb(&self) -> DynamicImage {
         let (width, height) = self.get_dimensions();
 
-        let mut new_img = DynamicImage::new_rgb8(width, height);
+        if is_high_precision(self) {
+            let pixels = float_pixels(&self.to_grayscale());
+            return from_float_pixels(width, height, pixels, self.color());
+        }
+
+        let mut new_img = new_like(self, width, height);
 
         for (x, y, pixel) in self.pixels() {
-            let new_l = ((0.299 * pixel[0] as f64) as u64
-                + (0.587 * pixel[1] as f64) as u64
-                + (0.114 * pixel[2] as f64) as u64) as u8;
+            let new_l = luma(pixel);
 
-            new_img.put_pixel(x, y, Rgba::from([new_l, new_l, new_l, 1]));
+            new_img.put_pixel(x, y, Rgba::from([new_l, new_l, new_l, pixel[3]]));
         }
 
         new_img
     }
 
     fn get_dimensions(&self) -> (u32, u32) {
-        let (ax, ay, bx, by) = self.bounds();
-        let width = bx - ax;
-        let height = by - ay;
-
-        (width, height)
+        self.dimensions()
     }
 
     fn quantize_grayscale(&self, qty: u8) -> DynamicImage {
+        // com no maximo 256 tons o resultado cabe em 8 bits
+        if is_high_precision(self) {
+            return to_8bit(self).quantize_grayscale(qty);
+        }
+
         let (width, height) = self.get_dimensions();
-        let mut grayscale = self.to_grayscale().as_luma8().unwrap().clone().into_raw();
-        let len = grayscale.len();
+        let gray = self.to_grayscale();
+        let mut grayscale = gray.to_luma8().into_raw();
         let (min, max) = {
             let mut tmp_max = 0;
             let mut tmp_min = 255;
@@ -124,46 +383,176 @@ impl ImageExt for DynamicImage {
         // (66 - min ) / 2 -> 1
         // ...
         // (196 - min) / 2 -> 64
-        for idx in 0..len {
+        for l in grayscale.iter_mut() {
             // 255
-            let l = grayscale[idx];
-            let bin_idx = (l - min) / bin_size;
+            let bin_idx = (*l - min) / bin_size;
 
             let bin_value = min + (bin_idx * bin_size);
-            grayscale[idx] = bin_value;
+            *l = bin_value;
+        }
+
+        let quantized = GrayImage::from_raw(width, height, grayscale).unwrap();
+
+        // reaplica o alfa original sobre os tons quantizados
+        if let DynamicImage::ImageLumaA8(mut new_img) = gray {
+            for (pixel, l) in new_img.pixels_mut().zip(quantized.pixels()) {
+                pixel[0] = l[0];
+            }
+
+            return DynamicImage::ImageLumaA8(new_img);
+        }
+
+        DynamicImage::ImageLuma8(quantized)
+    }
+
+    fn sample(&self, x: f32, y: f32, interpolation: Interpolation) -> Rgba<u8> {
+        let (w, h) = self.dimensions();
+
+        // coordenadas fora da imagem repetem a borda
+        let get = |x: i64, y: i64| {
+            let x = x.clamp(0, w as i64 - 1) as u32;
+            let y = y.clamp(0, h as i64 - 1) as u32;
+            self.get_pixel(x, y)
+        };
+
+        let weights = interpolation_weights(x, y, interpolation);
+        if interpolation == Interpolation::Nearest {
+            let (x, y, _) = weights[0];
+            return get(x, y);
         }
 
-        DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, grayscale).unwrap())
+        let samples: Vec<_> = weights
+            .iter()
+            .map(|(x, y, weight)| (get(*x, *y), *weight))
+            .collect();
+        blend(&samples)
+    }
+
+    fn rotate_90_cw(&self) -> DynamicImage {
+        map_buffer!(self, buffer => rotate_buffer_cw(buffer))
+    }
+
+    fn rotate_90_ccw(&self) -> DynamicImage {
+        map_buffer!(self, buffer => rotate_buffer_ccw(buffer))
+    }
+
+    fn rotate(
+        &self,
+        angle: f32,
+        interpolation: Interpolation,
+        canvas: RotateCanvas,
+    ) -> DynamicImage {
+        let (w, h) = self.dimensions();
+        // angulos positivos giram no sentido anti-horario, como no OpenCV
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        let (new_w, new_h) = match canvas {
+            RotateCanvas::Expand => (
+                (w as f32 * cos.abs() + h as f32 * sin.abs()).round() as u32,
+                (w as f32 * sin.abs() + h as f32 * cos.abs()).round() as u32,
+            ),
+            RotateCanvas::Crop => (w, h),
+        };
+
+        // 16 bits e HDR sao amostrados em ponto flutuante; o resultado volta para o
+        // tipo da imagem original, com os cantos fora da origem zerados
+        let high_precision = is_high_precision(self);
+        let src = if high_precision {
+            float_pixels(self)
+        } else {
+            vec![]
+        };
+
+        let mut pixels = vec![[0.0f32; 4]; new_w as usize * new_h as usize];
+        for (idx, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (idx as u32 % new_w, idx as u32 / new_w);
+
+            // mapeamento inverso: para cada pixel do destino buscamos sua origem
+            let dx = x as f32 + 0.5 - new_w as f32 / 2.0;
+            let dy = y as f32 + 0.5 - new_h as f32 / 2.0;
+
+            let src_x = cos * dx - sin * dy + w as f32 / 2.0 - 0.5;
+            let src_y = sin * dx + cos * dy + h as f32 / 2.0 - 0.5;
+
+            if src_x < -0.5 || src_y < -0.5 || src_x > w as f32 - 0.5 || src_y > h as f32 - 0.5 {
+                continue;
+            }
+
+            *pixel = if high_precision {
+                sample_float(&src, (w, h), src_x, src_y, interpolation)
+            } else {
+                let sampled = self.sample(src_x, src_y, interpolation);
+                sampled.0.map(|channel| channel as f32 / 255.0)
+            };
+        }
+
+        from_float_pixels(new_w, new_h, pixels, self.color())
     }
 }
 
-#[derive(Debug, Data, Clone, Copy, PartialEq)]
+#[derive(Debug, Data, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Operation {
     None,
     FlipH,
     FlipV,
-    Save,
     Grayscale,
     Quantize,
     AdjustBrightness,
     AdjustContrast,
     Negative,
+    Gamma,
+    ToneCurve(ToneCurve),
     ZoomOut,
     ZoomIn,
-    Convolution(Kernel),
+    Resize,
+    Convolution(Kernel, Normalization),
+    Equalize(EqualizeMode),
+    HistogramMatch,
+    RotateCw,
+    RotateCcw,
+    Rotate(Interpolation),
+    Canny,
+    Gradient(GradientOutput),
+    Pipeline(Pipeline),
 }
 
 pub trait ToDruidImage {
     fn to_druid_image(&self) -> Image;
 }
 
+// Lado de cada casa do xadrez desenhado atras das areas transparentes
+const CHECKER_SIZE: u32 = 8;
+
+// Compoe a imagem sobre um tabuleiro de xadrez cinza, deixando a transparencia visivel
+pub fn composite_checkerboard(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let background = if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) {
+            204.0
+        } else {
+            153.0
+        };
+
+        let alpha = pixel[3] as f32 / 255.0;
+        let over =
+            |channel: u8| (channel as f32 * alpha + background * (1.0 - alpha)).round() as u8;
+
+        image::Rgb([over(pixel[0]), over(pixel[1]), over(pixel[2])])
+    })
+}
+
 impl ToDruidImage for DynamicImage {
     fn to_druid_image(&self) -> Image {
-        let (ax, ay, bx, by) = self.bounds();
-        let width = bx - ax;
-        let height = by - ay;
+        let (width, height) = self.dimensions();
 
-        let pixels = self.to_rgb8().clone();
+        // 16 bits e HDR sao reduzidos a 8 bits apenas aqui, para exibicao
+        let pixels = if self.color().has_alpha() {
+            composite_checkerboard(self)
+        } else {
+            self.to_rgb8()
+        };
 
         Image::new(ImageBuf::from_raw(
             pixels.as_bytes(),
@@ -1,48 +1,829 @@
-use core::panic;
-use std::{
-    cmp::{max, min},
-    vec,
-};
+use std::{convert::TryFrom, fmt, sync::Arc, vec};
 
+use druid::Data;
 use image::{
-    DynamicImage, EncodableLayout, GenericImage, GenericImageView, GrayImage, Luma, Pixel,
-    RgbImage, Rgba,
+    DynamicImage, GenericImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::imageops::{cubic_weight, ImageExt};
+use crate::precision::{
+    self, bin, convert_to, float_luma, float_pixels, from_float_pixels, grayscale_color, is_float,
+    is_high_precision, map_color, unit_histogram, HIGH_PRECISION_BINS,
 };
 
-use crate::imageops::ImageExt;
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "KernelData")]
+pub enum Kernel {
+    // Kernel NxM com os pesos em ordem de linhas
+    Dense {
+        width: usize,
+        height: usize,
+        weights: Arc<Vec<f32>>,
+    },
+    // Kernel separavel, aplicado como duas passadas 1D
+    Separable {
+        horizontal: Arc<Vec<f32>>,
+        vertical: Arc<Vec<f32>>,
+    },
+}
+
+// Mesmo formato do Kernel no JSON, validado antes de virar um Kernel para que uma
+// receita com dimensoes invalidas seja recusada ao carregar, e nao na convolucao
+#[derive(Deserialize)]
+enum KernelData {
+    Dense {
+        width: usize,
+        height: usize,
+        weights: Vec<f32>,
+    },
+    Separable {
+        horizontal: Vec<f32>,
+        vertical: Vec<f32>,
+    },
+}
+
+impl TryFrom<KernelData> for Kernel {
+    type Error = KernelError;
+
+    fn try_from(data: KernelData) -> Result<Self, Self::Error> {
+        match data {
+            KernelData::Dense {
+                width,
+                height,
+                weights,
+            } => Kernel::try_new(width, height, weights),
+            KernelData::Separable {
+                horizontal,
+                vertical,
+            } => Kernel::try_separable(horizontal, vertical),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KernelError {
+    // Largura ou altura par ou zero
+    EvenDimensions(usize, usize),
+    // Quantidade de pesos diferente de largura x altura
+    WeightCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KernelError::EvenDimensions(width, height) => {
+                write!(f, "kernel dimensions must be odd, got {}x{}", width, height)
+            }
+            KernelError::WeightCount { expected, found } => {
+                write!(f, "kernel needs {} weights, got {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KernelError {}
+
+impl Data for Kernel {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl From<[f32; 9]> for Kernel {
+    fn from(weights: [f32; 9]) -> Self {
+        Kernel::new(3, 3, weights.to_vec())
+    }
+}
+
+impl Kernel {
+    pub fn new(width: usize, height: usize, weights: Vec<f32>) -> Kernel {
+        Kernel::try_new(width, height, weights).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(width: usize, height: usize, weights: Vec<f32>) -> Result<Kernel, KernelError> {
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(KernelError::EvenDimensions(width, height));
+        }
+        if weights.len() != width * height {
+            return Err(KernelError::WeightCount {
+                expected: width * height,
+                found: weights.len(),
+            });
+        }
+
+        Ok(Kernel::Dense {
+            width,
+            height,
+            weights: Arc::new(weights),
+        })
+    }
+
+    pub fn separable(horizontal: Vec<f32>, vertical: Vec<f32>) -> Kernel {
+        Kernel::try_separable(horizontal, vertical).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_separable(horizontal: Vec<f32>, vertical: Vec<f32>) -> Result<Kernel, KernelError> {
+        if horizontal.len().is_multiple_of(2) || vertical.len().is_multiple_of(2) {
+            return Err(KernelError::EvenDimensions(
+                horizontal.len(),
+                vertical.len(),
+            ));
+        }
+
+        Ok(Kernel::Separable {
+            horizontal: Arc::new(horizontal),
+            vertical: Arc::new(vertical),
+        })
+    }
+
+    pub fn gaussian(size: usize, sigma: f32) -> Kernel {
+        let radius = (size / 2) as f32;
+        let mut weights: Vec<f32> = (0..size)
+            .map(|i| {
+                let d = i as f32 - radius;
+                (-(d * d) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+        for weight in weights.iter_mut() {
+            *weight /= total;
+        }
+
+        Kernel::separable(weights.clone(), weights)
+    }
+
+    pub fn box_blur(size: usize) -> Kernel {
+        let weights = vec![1.0 / size as f32; size];
+        Kernel::separable(weights.clone(), weights)
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        match self {
+            Kernel::Dense { width, height, .. } => (*width, *height),
+            Kernel::Separable {
+                horizontal,
+                vertical,
+            } => (horizontal.len(), vertical.len()),
+        }
+    }
+
+    // Pesos em ordem de linhas; kernels separaveis viram o produto externo das passadas
+    pub fn weights(&self) -> Vec<f32> {
+        match self {
+            Kernel::Dense { weights, .. } => weights.to_vec(),
+            Kernel::Separable {
+                horizontal,
+                vertical,
+            } => vertical
+                .iter()
+                .flat_map(|v| horizontal.iter().map(move |h| h * v))
+                .collect(),
+        }
+    }
+}
+
+// Curva de tons definida por pontos de controle (entrada, saida) em 0..255, interpolados
+// por uma spline cubica natural. Antes do primeiro e depois do ultimo ponto a saida e constante
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct ToneCurve {
+    points: Arc<Vec<(f32, f32)>>,
+}
+
+impl Data for ToneCurve {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Default for ToneCurve {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<Vec<(f32, f32)>> for ToneCurve {
+    fn from(points: Vec<(f32, f32)>) -> Self {
+        ToneCurve::new(points)
+    }
+}
+
+impl From<ToneCurve> for Vec<(f32, f32)> {
+    fn from(curve: ToneCurve) -> Self {
+        curve.points.to_vec()
+    }
+}
+
+impl ToneCurve {
+    pub fn identity() -> ToneCurve {
+        ToneCurve::new(vec![(0.0, 0.0), (255.0, 255.0)])
+    }
+
+    // Limita os pontos a 0..255 e os ordena pela entrada; entre pontos com a mesma
+    // entrada fica o ultimo. Sem pontos a curva e a identidade
+    pub fn new(points: Vec<(f32, f32)>) -> ToneCurve {
+        if points.is_empty() {
+            return ToneCurve::identity();
+        }
+
+        let mut points: Vec<(f32, f32)> = points
+            .into_iter()
+            .map(|(x, y)| (x.clamp(0.0, 255.0), y.clamp(0.0, 255.0)))
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.reverse();
+        points.dedup_by(|a, b| a.0 == b.0);
+        points.reverse();
+
+        ToneCurve {
+            points: Arc::new(points),
+        }
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    // Segundas derivadas da spline em cada ponto, nulas nas pontas. O sistema e
+    // tridiagonal e resolvido pelo algoritmo de Thomas
+    fn second_derivatives(&self) -> Vec<f32> {
+        let points = &self.points;
+        let n = points.len();
+        let mut m = vec![0.0; n];
+        if n < 3 {
+            return m;
+        }
 
-pub type Kernel = [f32; 9];
+        let h: Vec<f32> = points.windows(2).map(|w| w[1].0 - w[0].0).collect();
+        let slope: Vec<f32> = points
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect();
+
+        let mut diag = vec![0.0; n];
+        let mut rhs = vec![0.0; n];
+        for i in 1..n - 1 {
+            diag[i] = 2.0 * (h[i - 1] + h[i]);
+            rhs[i] = 6.0 * (slope[i] - slope[i - 1]);
+
+            if i > 1 {
+                let factor = h[i - 1] / diag[i - 1];
+                diag[i] -= factor * h[i - 1];
+                rhs[i] -= factor * rhs[i - 1];
+            }
+        }
+
+        for i in (1..n - 1).rev() {
+            m[i] = (rhs[i] - h[i] * m[i + 1]) / diag[i];
+        }
+
+        m
+    }
+
+    fn interpolate(&self, m: &[f32], x: f32) -> f32 {
+        let points = &self.points;
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+
+        let i = points.partition_point(|p| p.0 <= x) - 1;
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+        let h = x1 - x0;
+        let a = (x1 - x) / h;
+        let b = (x - x0) / h;
+
+        a * y0 + b * y1 + ((a * a * a - a) * m[i] + (b * b * b - b) * m[i + 1]) * h * h / 6.0
+    }
+
+    // Curva amostrada em `bins` entradas igualmente espacadas, com as saidas em 0..1
+    pub fn values(&self, bins: usize) -> Vec<f32> {
+        let m = self.second_derivatives();
+        let step = 255.0 / (bins.max(2) - 1) as f32;
+
+        (0..bins)
+            .map(|idx| (self.interpolate(&m, idx as f32 * step) / 255.0).clamp(0.0, 1.0))
+            .collect()
+    }
 
-pub static GAUSSIANO: Kernel = [
+    // Tabela de 256 entradas usada em imagens de 8 bits
+    pub fn lut(&self) -> [u8; 256] {
+        let mut lut: [u8; 256] = [0; 256];
+        for (value, y) in lut.iter_mut().zip(self.values(256)) {
+            *value = (y * 255.0).round() as u8;
+        }
+
+        lut
+    }
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BorderMode {
+    // Pixels fora da imagem valem zero
+    Zero,
+    // Repete o pixel da borda: aaa|abcd|ddd
+    Clamp,
+    // Espelha a imagem na borda: cba|abcd|dcb
+    Reflect,
+    // Continua do lado oposto: bcd|abcd|abc
+    Wrap,
+}
+
+impl BorderMode {
+    // Converte uma coordenada possivelmente fora da imagem em uma coordenada valida
+    pub fn index(&self, i: i64, len: u32) -> Option<u32> {
+        let len = len as i64;
+        if (0..len).contains(&i) {
+            return Some(i as u32);
+        }
+
+        match self {
+            BorderMode::Zero => None,
+            BorderMode::Clamp => Some(i.clamp(0, len - 1) as u32),
+            BorderMode::Reflect => {
+                let period = 2 * len;
+                let i = i.rem_euclid(period);
+                Some(if i < len { i } else { period - 1 - i } as u32)
+            }
+            BorderMode::Wrap => Some(i.rem_euclid(len) as u32),
+        }
+    }
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConvolutionColor {
+    // Converte para tons de cinza antes de aplicar o kernel
+    Grayscale,
+    // Aplica o kernel em cada canal RGB de forma independente
+    PerChannel,
+    // Aplica o kernel apenas na luminancia (Y do YCbCr), preservando a cor
+    Luminance,
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Normalization {
+    // Limita o resultado a 0..255
+    Clamp,
+    // Soma 127 antes de limitar, para visualizar respostas negativas
+    Offset127,
+    // Usa o valor absoluto da resposta
+    Absolute,
+    // Reescala linearmente o menor e o maior valor para 0..255
+    MinMax,
+}
+
+pub static GAUSSIANO: [f32; 9] = [
     0.0625, 0.125, 0.0625, 0.125, 0.25, 0.125, 0.0625, 0.125, 0.0625,
 ];
 
-pub static LAPLACIANO: Kernel = [0.0, -1.0, 0.0, -1.0, 4.0, -1.0, 0.0, -1.0, 0.0];
+pub static LAPLACIANO: [f32; 9] = [0.0, -1.0, 0.0, -1.0, 4.0, -1.0, 0.0, -1.0, 0.0];
+
+pub static PASSA_ALTAS: [f32; 9] = [-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0];
+
+pub static SOBEL_HX: [f32; 9] = [-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0];
+
+pub static SOBEL_HY: [f32; 9] = [-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0];
+
+pub static PREWITT_HX: [f32; 9] = [-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0];
+
+pub static PREWITT_HY: [f32; 9] = [-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+
+pub static SCHARR_HX: [f32; 9] = [-3.0, 0.0, 3.0, -10.0, 0.0, 10.0, -3.0, 0.0, 3.0];
+
+pub static SCHARR_HY: [f32; 9] = [-3.0, -10.0, -3.0, 0.0, 0.0, 0.0, 3.0, 10.0, 3.0];
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GradientOperator {
+    Sobel,
+    Prewitt,
+    Scharr,
+}
+
+impl GradientOperator {
+    // Kernels (Hx, Hy) do operador
+    pub fn kernels(&self) -> ([f32; 9], [f32; 9]) {
+        match self {
+            GradientOperator::Sobel => (SOBEL_HX, SOBEL_HY),
+            GradientOperator::Prewitt => (PREWITT_HX, PREWITT_HY),
+            GradientOperator::Scharr => (SCHARR_HX, SCHARR_HY),
+        }
+    }
+
+    // Soma dos pesos positivos de Hx, usada para trazer a magnitude para 0..255
+    fn scale(&self) -> f32 {
+        self.kernels().0.iter().filter(|w| **w > 0.0).sum()
+    }
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GradientOutput {
+    // Magnitude do gradiente em tons de cinza
+    Magnitude,
+    // Pixels com magnitude acima do limiar em branco
+    Edges,
+    // Direcao do gradiente como matiz (HSV), com a magnitude como valor
+    Orientation,
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EqualizeMode {
+    // Equaliza a imagem em tons de cinza
+    Grayscale,
+    // Equaliza cada canal RGB de forma independente
+    PerChannel,
+    // Equaliza apenas a luminancia (Y do YCbCr), preservando a cor
+    Luminance,
+}
+
+#[derive(Debug, Data, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResizeFilter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn support(&self) -> f32 {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Bicubic => 2.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let sinc = |x: f32| {
+            if x == 0.0 {
+                1.0
+            } else {
+                let x = x * std::f32::consts::PI;
+                x.sin() / x
+            }
+        };
+
+        match self {
+            ResizeFilter::Nearest => {
+                if x.abs() < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x.abs()).max(0.0),
+            ResizeFilter::Bicubic => cubic_weight(x),
+            ResizeFilter::Lanczos3 => {
+                if x.abs() < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// Para cada posicao do destino, os indices da origem e seus pesos normalizados
+fn resample_weights(src_len: u32, dst_len: u32, filter: ResizeFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale = src_len as f32 / dst_len as f32;
+    // ao reduzir, o filtro eh alargado para cobrir todos os pixels da origem
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+    let last = src_len as i64 - 1;
+
+    (0..dst_len)
+        .map(|dst| {
+            let center = (dst as f32 + 0.5) * scale;
+
+            if filter == ResizeFilter::Nearest {
+                let idx = (center.floor() as i64).clamp(0, last) as usize;
+                return vec![(idx, 1.0)];
+            }
+
+            let left = (center - support).floor() as i64;
+            let right = (center + support).ceil() as i64;
+
+            let mut taps: Vec<(usize, f32)> = (left..=right)
+                .map(|src| {
+                    let weight = filter.weight((src as f32 + 0.5 - center) / filter_scale);
+                    (src.clamp(0, last) as usize, weight)
+                })
+                .filter(|(_, weight)| *weight != 0.0)
+                .collect();
+
+            let total: f32 = taps.iter().map(|(_, weight)| weight).sum();
+            for (_, weight) in taps.iter_mut() {
+                *weight /= total;
+            }
+
+            taps
+        })
+        .collect()
+}
+
+pub fn cumulative_histogram(histogram: &[u32; 256]) -> [u32; 256] {
+    let mut cumulative: [u32; 256] = [0; 256];
+    let mut acc = 0;
+
+    for (idx, count) in histogram.iter().enumerate() {
+        acc += count;
+        cumulative[idx] = acc;
+    }
+
+    cumulative
+}
+
+fn equalization_lut(histogram: &[u32; 256]) -> [u8; 256] {
+    let cumulative = cumulative_histogram(histogram);
+    let total = cumulative[255].max(1) as f64;
+    let alpha = 255.0 / total;
+
+    let mut lut: [u8; 256] = [0; 256];
+    for (idx, count) in cumulative.iter().enumerate() {
+        lut[idx] = (alpha * *count as f64).round().clamp(0.0, 255.0) as u8;
+    }
+
+    lut
+}
+
+// Aplica a tabela aos tons de cinza da imagem, mantendo o alfa se houver
+fn map_luma(image: &DynamicImage, lut: &[u8; 256]) -> DynamicImage {
+    match image.to_grayscale() {
+        DynamicImage::ImageLumaA8(mut grayscale) => {
+            for pixel in grayscale.pixels_mut() {
+                pixel[0] = lut[pixel[0] as usize];
+            }
+
+            DynamicImage::ImageLumaA8(grayscale)
+        }
+        grayscale => {
+            let mut grayscale = grayscale.into_luma8();
+            for l in grayscale.iter_mut() {
+                *l = lut[*l as usize];
+            }
+
+            DynamicImage::ImageLuma8(grayscale)
+        }
+    }
+}
+
+// Aplica a tabela a cada canal de cor, mantendo o alfa e o tipo da imagem
+fn map_channels(image: &DynamicImage, lut: &[u8; 256]) -> DynamicImage {
+    let mut new_img = image.clone();
+
+    for (x, y, pixel) in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        new_img.put_pixel(
+            x,
+            y,
+            Rgba::from([lut[r as usize], lut[g as usize], lut[b as usize], a]),
+        );
+    }
+
+    new_img
+}
+
+// h em graus, s e v em 0..1
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let c = v * s;
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    let m = v - c;
+    let to_u8 = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+pub fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    rgb_to_ycbcr_f32(r as f32, g as f32, b as f32)
+}
+
+// Versao sem arredondamento, com os canais em 0..255
+pub fn rgb_to_ycbcr_f32(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+
+    (y, cb, cr)
+}
+
+pub fn ycbcr_to_rgb(y: f32, cb: f32, cr: f32) -> (u8, u8, u8) {
+    let (r, g, b) = ycbcr_to_rgb_f32(y, cb, cr);
+
+    (
+        r.round().clamp(0.0, 255.0) as u8,
+        g.round().clamp(0.0, 255.0) as u8,
+        b.round().clamp(0.0, 255.0) as u8,
+    )
+}
+
+pub fn ycbcr_to_rgb_f32(y: f32, cb: f32, cr: f32) -> (f32, f32, f32) {
+    let r = y + 1.402 * (cr - 128.0);
+    let g = y - 0.344_136 * (cb - 128.0) - 0.714_136 * (cr - 128.0);
+    let b = y + 1.772 * (cb - 128.0);
+
+    (r, g, b)
+}
+
+// Equalizacao de imagens de 16 bits e HDR, com 65536 tons por canal
+fn histogram_equalize_high_precision(image: &DynamicImage, mode: EqualizeMode) -> DynamicImage {
+    let (w, h) = image.dimensions();
+    let mut pixels = float_pixels(image);
+    let visible = |pixel: &&[f32; 4]| pixel[3] > 0.0;
+
+    match mode {
+        EqualizeMode::Grayscale => {
+            let luma: Vec<f32> = pixels.iter().map(float_luma).collect();
+            let histogram = unit_histogram(
+                pixels.iter().filter(visible).map(float_luma),
+                HIGH_PRECISION_BINS,
+            );
+            let lut = precision::equalization_lut(&histogram);
+
+            for (pixel, l) in pixels.iter_mut().zip(luma) {
+                let l = lut[bin(l, HIGH_PRECISION_BINS)];
+                *pixel = [l, l, l, pixel[3]];
+            }
+
+            from_float_pixels(w, h, pixels, grayscale_color(image))
+        }
+        EqualizeMode::PerChannel => {
+            let luts: Vec<Vec<f32>> = (0..3)
+                .map(|channel| {
+                    let values = pixels.iter().filter(visible).map(|p| p[channel]);
+                    precision::equalization_lut(&unit_histogram(values, HIGH_PRECISION_BINS))
+                })
+                .collect();
+
+            for pixel in pixels.iter_mut() {
+                for (value, lut) in pixel.iter_mut().zip(luts.iter()) {
+                    *value = lut[bin(*value, HIGH_PRECISION_BINS)];
+                }
+            }
 
-pub static PASSA_ALTAS: Kernel = [-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0];
+            from_float_pixels(w, h, pixels, image.color())
+        }
+        EqualizeMode::Luminance => {
+            let ycbcr = |p: &[f32; 4]| rgb_to_ycbcr_f32(p[0] * 255.0, p[1] * 255.0, p[2] * 255.0);
+            let values = pixels.iter().filter(visible).map(|p| ycbcr(p).0 / 255.0);
+            let lut = precision::equalization_lut(&unit_histogram(values, HIGH_PRECISION_BINS));
+
+            for pixel in pixels.iter_mut() {
+                let (l, cb, cr) = ycbcr(pixel);
+                let l = lut[bin(l / 255.0, HIGH_PRECISION_BINS)] * 255.0;
+                let (r, g, b) = ycbcr_to_rgb_f32(l, cb, cr);
+                *pixel = [r / 255.0, g / 255.0, b / 255.0, pixel[3]];
+            }
+
+            from_float_pixels(w, h, pixels, image.color())
+        }
+    }
+}
+
+// Histograma do Y do YCbCr, ignorando pixels transparentes
+fn ycbcr_histogram(image: &DynamicImage) -> [u32; 256] {
+    let mut histogram: [u32; 256] = [0; 256];
+
+    for (_, _, pixel) in image.pixels().filter(|(_, _, p)| p[3] > 0) {
+        let (y, _, _) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+        histogram[y.round().clamp(0.0, 255.0) as usize] += 1;
+    }
+
+    histogram
+}
+
+// Aplica a tabela no Y do YCbCr, mantendo o Cb, o Cr e o alfa
+fn map_ycbcr_luma(image: &DynamicImage, lut: &[u8; 256]) -> DynamicImage {
+    let mut new_img = image.clone();
+    for (x, y, pixel) in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let (l, cb, cr) = rgb_to_ycbcr(r, g, b);
+        let l = lut[l.round().clamp(0.0, 255.0) as usize] as f32;
+        let (r, g, b) = ycbcr_to_rgb(l, cb, cr);
+
+        new_img.put_pixel(x, y, Rgba::from([r, g, b, a]));
+    }
+
+    new_img
+}
+
+// Para cada tom da origem, procura o tom do alvo com a frequencia acumulada mais proxima
+fn match_lut(source: &[u32; 256], target: &[u32; 256]) -> [u8; 256] {
+    let src_cumulative = cumulative_histogram(source);
+    let target_cumulative = cumulative_histogram(target);
+
+    let src_total = src_cumulative[255].max(1) as f64;
+    let target_total = target_cumulative[255].max(1) as f64;
+
+    let mut lut: [u8; 256] = [0; 256];
+    for (idx, count) in src_cumulative.iter().enumerate() {
+        let src_freq = *count as f64 / src_total;
+
+        let mut best = 0;
+        let mut best_diff = f64::MAX;
+        for (target_idx, target_count) in target_cumulative.iter().enumerate() {
+            let diff = (src_freq - *target_count as f64 / target_total).abs();
+            if diff < best_diff {
+                best_diff = diff;
+                best = target_idx;
+            }
+        }
+
+        lut[idx] = best as u8;
+    }
+
+    lut
+}
+
+// Histograma de luminancia com 65536 tons, ignorando pixels transparentes
+fn luma_histogram_high_precision(image: &DynamicImage) -> Vec<u32> {
+    unit_histogram(
+        float_pixels(image)
+            .iter()
+            .filter(|pixel| pixel[3] > 0.0)
+            .map(float_luma),
+        HIGH_PRECISION_BINS,
+    )
+}
 
 pub trait ImageExt2 {
+    fn grayscale_histogram(&self) -> [u32; 256];
     fn render_grayscale_histogram(&self) -> DynamicImage;
-    fn adjust_brightness(&self, val: u8) -> DynamicImage;
-    fn adjust_contrast_2(&self, val: u8) -> DynamicImage;
+    fn histogram_equalize(&self, mode: EqualizeMode) -> DynamicImage;
+    fn histogram_match(&self, target: &DynamicImage) -> DynamicImage;
+    // Soma `val` (-255..255) a cada canal de cor
+    fn adjust_brightness(&self, val: i16) -> DynamicImage;
+    // Multiplica cada canal de cor pelo ganho `gain`
+    fn adjust_contrast_2(&self, gain: f32) -> DynamicImage;
     fn negative(&self) -> DynamicImage;
-    fn zoom_out(&self, x: u8, y: u8) -> DynamicImage;
-    fn zoom_in(&self) -> DynamicImage;
-    fn convolution(&self, kernel: Kernel) -> DynamicImage;
+    // Eleva cada canal de cor, em 0..1, a 1 / gamma; gamma acima de 1 clareia os tons medios
+    fn gamma_correction(&self, gamma: f32) -> DynamicImage;
+    fn tone_curve(&self, curve: &ToneCurve) -> DynamicImage;
+    // DynamicImage::resize tem o mesmo nome, chame como ImageExt2::resize(&img, ...)
+    fn resize(&self, new_w: u32, new_h: u32, filter: ResizeFilter) -> DynamicImage;
+    fn convolution(
+        &self,
+        kernel: &Kernel,
+        border: BorderMode,
+        color: ConvolutionColor,
+        normalization: Normalization,
+    ) -> DynamicImage;
+    fn canny(&self, low: f32, high: f32, sigma: f32) -> DynamicImage;
+    fn gradient(
+        &self,
+        operator: GradientOperator,
+        output: GradientOutput,
+        threshold: f32,
+    ) -> DynamicImage;
 }
 
 impl ImageExt2 for DynamicImage {
-    fn render_grayscale_histogram(&self) -> DynamicImage {
-        let grayscale = self.to_grayscale();
+    fn grayscale_histogram(&self) -> [u32; 256] {
+        // o histograma exibido tem sempre 256 colunas; os de 16 bits sao agrupados
+        if is_high_precision(self) {
+            let mut histogram: [u32; 256] = [0; 256];
+            for (idx, count) in luma_histogram_high_precision(self).iter().enumerate() {
+                histogram[idx * 256 / HIGH_PRECISION_BINS] += count;
+            }
+
+            return histogram;
+        }
+
+        let grayscale = self.to_grayscale().to_luma_alpha8();
         let mut histogram: [u32; 256] = [0; 256];
 
+        for pixel in grayscale.pixels() {
+            // pixels totalmente transparentes nao aparecem na imagem
+            if pixel[1] > 0 {
+                histogram[pixel[0] as usize] += 1;
+            }
+        }
+
+        histogram
+    }
+
+    fn render_grayscale_histogram(&self) -> DynamicImage {
+        let histogram = self.grayscale_histogram();
+
         let (w, h) = self.dimensions();
         let size = w * h;
 
-        for l in grayscale.as_bytes() {
-            histogram[*l as usize] += 1;
-        }
-
         // maximum value is going to be our full column
         let max_val = histogram.iter().max().unwrap();
         let pixel_value = (*max_val as f64) / 255.0;
@@ -56,28 +837,131 @@ impl ImageExt2 for DynamicImage {
             }
         }
 
-        debug_assert!((|| {
+        debug_assert!({
             let column_sum: usize = histogram
                 .iter()
-                .fold(0, |acc: usize, cur| (acc + (*cur as usize)) as usize);
+                .fold(0, |acc: usize, cur| acc + (*cur as usize));
 
-            column_sum == size as usize
-        })());
+            column_sum <= size as usize
+        });
 
         image::DynamicImage::ImageLuma8(result_image)
     }
 
-    fn adjust_brightness(&self, val: u8) -> DynamicImage {
+    fn histogram_equalize(&self, mode: EqualizeMode) -> DynamicImage {
+        if is_high_precision(self) {
+            return histogram_equalize_high_precision(self, mode);
+        }
+
+        match mode {
+            EqualizeMode::Grayscale => {
+                let lut = equalization_lut(&self.grayscale_histogram());
+                map_luma(self, &lut)
+            }
+            EqualizeMode::PerChannel => {
+                let mut histograms: [[u32; 256]; 3] = [[0; 256]; 3];
+
+                for (_, _, pixel) in self.pixels().filter(|(_, _, p)| p[3] > 0) {
+                    for (channel, histogram) in histograms.iter_mut().enumerate() {
+                        histogram[pixel[channel] as usize] += 1;
+                    }
+                }
+
+                let luts = [
+                    equalization_lut(&histograms[0]),
+                    equalization_lut(&histograms[1]),
+                    equalization_lut(&histograms[2]),
+                ];
+
+                let mut new_img = self.clone();
+                for (x, y, pixel) in self.pixels() {
+                    let [r, g, b, a] = pixel.0;
+                    new_img.put_pixel(
+                        x,
+                        y,
+                        Rgba::from([
+                            luts[0][r as usize],
+                            luts[1][g as usize],
+                            luts[2][b as usize],
+                            a,
+                        ]),
+                    );
+                }
+
+                new_img
+            }
+            EqualizeMode::Luminance => {
+                map_ycbcr_luma(self, &equalization_lut(&ycbcr_histogram(self)))
+            }
+        }
+    }
+
+    fn histogram_match(&self, target: &DynamicImage) -> DynamicImage {
+        // imagens coloridas casam so a luminancia e mantem o Cb e o Cr
+        let color = self.color().has_color();
+
+        if is_high_precision(self) {
+            let (w, h) = self.dimensions();
+            let mut pixels = float_pixels(self);
+
+            if !color {
+                let lut = precision::match_lut(
+                    &luma_histogram_high_precision(self),
+                    &luma_histogram_high_precision(target),
+                );
+
+                for pixel in pixels.iter_mut() {
+                    let l = lut[bin(float_luma(pixel), HIGH_PRECISION_BINS)];
+                    *pixel = [l, l, l, pixel[3]];
+                }
+
+                return from_float_pixels(w, h, pixels, grayscale_color(self));
+            }
+
+            let ycbcr = |p: &[f32; 4]| rgb_to_ycbcr_f32(p[0] * 255.0, p[1] * 255.0, p[2] * 255.0);
+            let values = pixels
+                .iter()
+                .filter(|pixel| pixel[3] > 0.0)
+                .map(|p| ycbcr(p).0 / 255.0);
+            let lut = precision::match_lut(
+                &unit_histogram(values, HIGH_PRECISION_BINS),
+                &luma_histogram_high_precision(target),
+            );
+
+            for pixel in pixels.iter_mut() {
+                let (l, cb, cr) = ycbcr(pixel);
+                let l = lut[bin(l / 255.0, HIGH_PRECISION_BINS)] * 255.0;
+                let (r, g, b) = ycbcr_to_rgb_f32(l, cb, cr);
+                *pixel = [r / 255.0, g / 255.0, b / 255.0, pixel[3]];
+            }
+
+            return from_float_pixels(w, h, pixels, self.color());
+        }
+
+        if !color {
+            let lut = match_lut(&self.grayscale_histogram(), &target.grayscale_histogram());
+            return map_luma(self, &lut);
+        }
+
+        let lut = match_lut(&ycbcr_histogram(self), &ycbcr_histogram(target));
+        map_ycbcr_luma(self, &lut)
+    }
+
+    fn adjust_brightness(&self, val: i16) -> DynamicImage {
+        if is_high_precision(self) {
+            return map_color(self, |p| p + val as f32 / 255.0);
+        }
+
         let mut new_img = self.clone();
 
         let adjust_pixel = |p: u8| {
-            let result = min(255, max(0, p as i32 + val as i32));
-            debug_assert!(result <= 255 && result >= 0);
+            let result = (p as i32 + val as i32).clamp(0, 255);
+            debug_assert!((0..=255).contains(&result));
             result as u8
         };
 
         for (x, y, pixel) in self.pixels() {
-            let (r, g, b, a) = pixel.channels4();
+            let [r, g, b, a] = pixel.0;
             new_img.put_pixel(
                 x,
                 y,
@@ -88,17 +972,19 @@ impl ImageExt2 for DynamicImage {
         new_img
     }
 
-    fn adjust_contrast_2(&self, val: u8) -> DynamicImage {
+    fn adjust_contrast_2(&self, gain: f32) -> DynamicImage {
+        let gain = gain.max(0.0);
+
+        if is_high_precision(self) {
+            return map_color(self, |p| p * gain);
+        }
+
         let mut new_img = self.clone();
 
-        let adjust_pixel = |p: u8| {
-            let result = min(255, max(0, p as i32 * val as i32));
-            debug_assert!(result <= 255 && result >= 0);
-            result as u8
-        };
+        let adjust_pixel = |p: u8| (p as f32 * gain).round().clamp(0.0, 255.0) as u8;
 
         for (x, y, pixel) in self.pixels() {
-            let (r, g, b, a) = pixel.channels4();
+            let [r, g, b, a] = pixel.0;
             new_img.put_pixel(
                 x,
                 y,
@@ -110,15 +996,16 @@ impl ImageExt2 for DynamicImage {
     }
 
     fn negative(&self) -> DynamicImage {
+        if is_high_precision(self) {
+            return map_color(self, |p| (1.0 - p).max(0.0));
+        }
+
         let mut new_img = self.clone();
 
-        let adjust_pixel = |p: u8| {
-            let result = min(255, max(0, 255 - p));
-            result
-        };
+        let adjust_pixel = |p: u8| 255 - p;
 
         for (x, y, pixel) in self.pixels() {
-            let (r, g, b, a) = pixel.channels4();
+            let [r, g, b, a] = pixel.0;
             new_img.put_pixel(
                 x,
                 y,
@@ -129,180 +1016,492 @@ impl ImageExt2 for DynamicImage {
         new_img
     }
 
-    fn zoom_out(&self, scaling_w: u8, scaling_h: u8) -> DynamicImage {
-        let (w, h) = self.dimensions();
-        let src_bytes = self.to_rgb8();
+    fn gamma_correction(&self, gamma: f32) -> DynamicImage {
+        let exponent = 1.0 / gamma.max(0.01);
 
-        let mut buffer: Vec<u8> = vec![];
-        let new_w = w / scaling_w as u32;
-        let new_h = h / scaling_h as u32;
+        if is_high_precision(self) {
+            return map_color(self, |p| p.max(0.0).powf(exponent));
+        }
 
-        let rows = src_bytes
-            .as_bytes()
-            .chunks_exact((w * scaling_h as u32 * 3) as usize);
+        let mut lut: [u8; 256] = [0; 256];
+        for (idx, value) in lut.iter_mut().enumerate() {
+            *value = ((idx as f32 / 255.0).powf(exponent) * 255.0).round() as u8;
+        }
 
-        let get_block = |row: &[u8], offset: usize| {
-            // Get a block of MxN pixels from some source as a flat array
-            // M => scaling_H
-            // N => scanling_w
-            let mut buf = Vec::with_capacity((scaling_w * scaling_h * 3) as usize);
+        map_channels(self, &lut)
+    }
+
+    fn tone_curve(&self, curve: &ToneCurve) -> DynamicImage {
+        // com mais de 8 bits a curva e amostrada com a mesma resolucao dos histogramas
+        if is_high_precision(self) {
+            let values = curve.values(HIGH_PRECISION_BINS);
+            return map_color(self, |p| values[bin(p, HIGH_PRECISION_BINS)]);
+        }
 
-            for i in 0..scaling_h as usize {
-                let col_idx = (i * 3 * w as usize) + offset;
+        map_channels(self, &curve.lut())
+    }
 
-                for j in 0..(scaling_w as usize * 3) {
-                    let value = row[col_idx + j];
-                    buf.push(value);
+    fn resize(&self, new_w: u32, new_h: u32, filter: ResizeFilter) -> DynamicImage {
+        let (w, h) = self.dimensions();
+        let high_precision = is_high_precision(self);
+        // canais em 0..255, em ponto flutuante para manter 16 bits e HDR
+        let src: Vec<[f32; 4]> = if high_precision {
+            float_pixels(self)
+                .iter()
+                .map(|p| [p[0] * 255.0, p[1] * 255.0, p[2] * 255.0, p[3] * 255.0])
+                .collect()
+        } else {
+            self.to_rgba8()
+                .pixels()
+                .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32])
+                .collect()
+        };
+        let new_w = new_w.max(1);
+        let new_h = new_h.max(1);
+
+        let x_weights = resample_weights(w, new_w, filter);
+        let y_weights = resample_weights(h, new_h, filter);
+
+        // Passada horizontal: w x h -> new_w x h
+        let mut horizontal = vec![[0.0f32; 4]; (new_w * h) as usize];
+        for y in 0..h {
+            for (x, taps) in x_weights.iter().enumerate() {
+                let acc = &mut horizontal[(y * new_w) as usize + x];
+                for (src_x, weight) in taps {
+                    // cores pre-multiplicadas pelo alfa, para nao espalhar a cor de pixels
+                    // transparentes pelas bordas
+                    let pixel = src[(y * w) as usize + src_x];
+                    let alpha = pixel[3] / 255.0;
+                    for (channel, value) in acc.iter_mut().take(3).enumerate() {
+                        *value += pixel[channel] * alpha * weight;
+                    }
+                    acc[3] += pixel[3] * weight;
                 }
             }
-            debug_assert!(buf.len() == (scaling_w * scaling_h * 3) as usize);
-            buf
-        };
+        }
+
+        // Passada vertical: new_w x h -> new_w x new_h
+        let mut new_img = RgbaImage::new(new_w, new_h);
+        let mut float_img = vec![[0.0f32; 4]; (new_w * new_h) as usize];
+        for (y, taps) in y_weights.iter().enumerate() {
+            for x in 0..new_w {
+                let mut acc = [0.0f32; 4];
+                for (src_y, weight) in taps {
+                    let pixel = horizontal[*src_y * new_w as usize + x as usize];
+                    for (channel, value) in acc.iter_mut().enumerate() {
+                        *value += pixel[channel] * weight;
+                    }
+                }
 
-        for row in rows {
-            for offset in (0..(3 * w) as usize).step_by((scaling_w * 3) as usize) {
-                let block = get_block(row, offset as usize);
-                let mut r = 0;
-                let mut g = 0;
-                let mut b = 0;
-
-                for pixel in block.chunks_exact(3) {
-                    r += pixel[0] as u32;
-                    g += pixel[1] as u32;
-                    b += pixel[2] as u32;
+                let alpha = acc[3].clamp(0.0, 255.0);
+                if alpha <= 0.0 {
+                    continue;
                 }
 
-                let block_size = scaling_h as u32 * scaling_w as u32;
-                buffer.push((r / block_size) as u8);
-                buffer.push((g / block_size) as u8);
-                buffer.push((b / block_size) as u8);
+                if high_precision {
+                    let color = |value: f32| (value / alpha).max(0.0);
+                    float_img[y * new_w as usize + x as usize] =
+                        [color(acc[0]), color(acc[1]), color(acc[2]), alpha / 255.0];
+                    continue;
+                }
+
+                let color = |value: f32| (value * 255.0 / alpha).round().clamp(0.0, 255.0) as u8;
+                new_img.put_pixel(
+                    x,
+                    y as u32,
+                    Rgba::from([
+                        color(acc[0]),
+                        color(acc[1]),
+                        color(acc[2]),
+                        alpha.round() as u8,
+                    ]),
+                );
             }
         }
 
-        let image = RgbImage::from_raw(new_w, new_h, buffer).unwrap();
+        if high_precision {
+            return from_float_pixels(new_w, new_h, float_img, self.color());
+        }
 
-        DynamicImage::ImageRgb8(image)
+        // o calculo e feito em RGBA, mas o resultado volta para o tipo da imagem original
+        convert_to(&DynamicImage::ImageRgba8(new_img), self.color())
     }
 
-    fn zoom_in(&self) -> DynamicImage {
+    fn convolution(
+        &self,
+        kernel: &Kernel,
+        border: BorderMode,
+        color: ConvolutionColor,
+        normalization: Normalization,
+    ) -> DynamicImage {
         let (w, h) = self.dimensions();
-        let mut new_img = image::DynamicImage::new_rgb8((w * 2) - 1, (h * 2) - 1);
-        let empty = new_img.clone();
-
-        let interpolate = |x: Rgba<u8>, y: Rgba<u8>| {
-            let x = x.channels();
-            let y = y.channels();
 
-            let new_r = ((x[0] as u32 + y[0] as u32) / 2) as u8;
-            let new_g = ((x[1] as u32 + y[1] as u32) / 2) as u8;
-            let new_b = ((x[2] as u32 + y[2] as u32) / 2) as u8;
+        // Planos de entrada e quantos deles passam pelo kernel
+        let (planes, channels): (Vec<[f32; 3]>, usize) = match color {
+            ConvolutionColor::Grayscale => (luma_planes(self), 1),
+            ConvolutionColor::PerChannel => (color_planes(self), 3),
+            ConvolutionColor::Luminance => (
+                color_planes(self)
+                    .iter()
+                    .map(|p| {
+                        let (y, cb, cr) = rgb_to_ycbcr_f32(p[0], p[1], p[2]);
+                        [y, cb, cr]
+                    })
+                    .collect(),
+                1,
+            ),
+        };
 
-            Rgba::from([new_r, new_g, new_b, 1])
+        let sums = convolve_planes(&planes, w, h, kernel, border, channels);
+
+        let (min, max) = sums
+            .iter()
+            .flat_map(|sum| sum[..channels].iter())
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            });
+
+        let normalize = |value: f32| match normalization {
+            Normalization::Clamp => value,
+            Normalization::Offset127 => value + 127.0,
+            Normalization::Absolute => value.abs(),
+            Normalization::MinMax if max > min => (value - min) * 255.0 / (max - min),
+            Normalization::MinMax => value,
         };
 
-        for (x, y, pixel) in self.pixels() {
-            new_img.put_pixel(x * 2, y * 2, pixel);
+        // 16 bits e HDR voltam sem arredondar; HDR so e limitado abaixo de zero
+        if is_high_precision(self) {
+            let limit = if is_float(self) { f32::MAX } else { 255.0 };
+            let level = |value: f32| normalize(value).clamp(0.0, limit) / 255.0;
+
+            let pixels = float_pixels(self)
+                .iter()
+                .zip(sums.iter())
+                .zip(planes.iter())
+                .map(|((src, sum), plane)| match color {
+                    ConvolutionColor::Grayscale => {
+                        let l = level(sum[0]);
+                        [l, l, l, src[3]]
+                    }
+                    ConvolutionColor::PerChannel => {
+                        [level(sum[0]), level(sum[1]), level(sum[2]), src[3]]
+                    }
+                    ConvolutionColor::Luminance => {
+                        let (r, g, b) = ycbcr_to_rgb_f32(level(sum[0]) * 255.0, plane[1], plane[2]);
+                        let channel = |value: f32| (value / 255.0).clamp(0.0, limit / 255.0);
+                        [channel(r), channel(g), channel(b), src[3]]
+                    }
+                })
+                .collect();
+
+            return from_float_pixels(w, h, pixels, self.color());
         }
 
-        for (x, y, _) in empty.pixels() {
-            // Preenchemos as colunas impares e linhas pares
-            if ((x % 2) == 1) && ((y % 2) == 0) {
-                let after = new_img.get_pixel(x + 1, y);
-                let before = new_img.get_pixel(x - 1, y);
-                new_img.put_pixel(x, y, interpolate(before, after));
-            }
+        let clamp = |value: f32| normalize(value).round().clamp(0.0, 255.0) as u8;
 
-            // Preenchemos as colunas pares e linhas impares
-            if ((x % 2) == 0) && ((y % 2) == 1) {
-                let after = new_img.get_pixel(x, y + 1);
-                let before = new_img.get_pixel(x, y - 1);
-                new_img.put_pixel(x, y, interpolate(before, after));
-            }
+        let mut new_img = RgbImage::new(w, h);
+        for ((pixel, sum), plane) in new_img.pixels_mut().zip(sums.iter()).zip(planes.iter()) {
+            *pixel = match color {
+                ConvolutionColor::Grayscale => {
+                    let l = clamp(sum[0]);
+                    Rgb::from([l, l, l])
+                }
+                ConvolutionColor::PerChannel => {
+                    Rgb::from([clamp(sum[0]), clamp(sum[1]), clamp(sum[2])])
+                }
+                ConvolutionColor::Luminance => {
+                    let (r, g, b) = ycbcr_to_rgb(clamp(sum[0]) as f32, plane[1], plane[2]);
+                    Rgb::from([r, g, b])
+                }
+            };
         }
 
-        for (x, y, _) in empty.pixels() {
-            // Preenchemos as colunas impares e linhas impares
-            if ((x % 2) == 1) && ((y % 2) == 1) {
-                let after = new_img.get_pixel(x, y + 1);
-                let before = new_img.get_pixel(x, y - 1);
-                let y_interpolated = interpolate(before, after);
+        // o kernel atua apenas nas cores; o alfa da entrada e mantido
+        if self.color().has_alpha() {
+            let alpha = self.to_rgba8();
+            let new_img = RgbaImage::from_fn(w, h, |x, y| {
+                let [r, g, b] = new_img.get_pixel(x, y).0;
+                Rgba::from([r, g, b, alpha.get_pixel(x, y)[3]])
+            });
+
+            return DynamicImage::ImageRgba8(new_img);
+        }
 
-                let after = new_img.get_pixel(x + 1, y);
-                let before = new_img.get_pixel(x - 1, y);
-                let x_interpolated = interpolate(after, before);
+        DynamicImage::ImageRgb8(new_img)
+    }
 
-                let interpolated = interpolate(x_interpolated, y_interpolated);
-                new_img.put_pixel(x, y, interpolated);
+    // Mapas de bordas sao opacos: a transparencia da entrada e ignorada
+    fn canny(&self, low: f32, high: f32, sigma: f32) -> DynamicImage {
+        let (w, h) = self.dimensions();
+        let high = high.max(low);
+        let planes = luma_planes(self);
+
+        // 1. suavizacao gaussiana, com raio de 3 sigmas
+        let size = 2 * (3.0 * sigma).ceil().max(1.0) as usize + 1;
+        let smoothed = convolve_planes(
+            &planes,
+            w,
+            h,
+            &Kernel::gaussian(size, sigma),
+            BorderMode::Clamp,
+            1,
+        );
+
+        // 2. gradientes de Sobel
+        let gradients = gradients(&smoothed, w, h, SOBEL_HX, SOBEL_HY);
+        let magnitude: Vec<f32> = gradients.iter().map(|(gx, gy)| gx.hypot(*gy)).collect();
+
+        // 3. supressao de nao maximos na direcao do gradiente
+        let mut thin = vec![0.0f32; (w * h) as usize];
+        for y in 1..h.saturating_sub(1) {
+            for x in 1..w.saturating_sub(1) {
+                let idx = (y * w + x) as usize;
+                let (gx, gy) = gradients[idx];
+
+                let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+                let (dx, dy): (i64, i64) = if !(22.5..157.5).contains(&angle) {
+                    (1, 0)
+                } else if angle < 67.5 {
+                    (1, 1)
+                } else if angle < 112.5 {
+                    (0, 1)
+                } else {
+                    (-1, 1)
+                };
+
+                let neighbor = |dx: i64, dy: i64| {
+                    magnitude[((y as i64 + dy) * w as i64 + x as i64 + dx) as usize]
+                };
+
+                let m = magnitude[idx];
+                if m >= neighbor(dx, dy) && m >= neighbor(-dx, -dy) {
+                    thin[idx] = m;
+                }
             }
         }
-        new_img
+
+        // 4. histerese: bordas fortes propagam para vizinhas fracas conectadas
+        let mut edges = GrayImage::new(w, h);
+        let mut stack: Vec<(u32, u32)> = vec![];
+        for (idx, m) in thin.iter().enumerate() {
+            if *m >= high {
+                let (x, y) = (idx as u32 % w, idx as u32 / w);
+                edges.put_pixel(x, y, Luma([255]));
+                stack.push((x, y));
+            }
+        }
+
+        while let Some((x, y)) = stack.pop() {
+            for dy in -1i64..=1 {
+                for dx in -1i64..=1 {
+                    let nx = x as i64 + dx;
+                    let ny = y as i64 + dy;
+                    if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                        continue;
+                    }
+
+                    let (nx, ny) = (nx as u32, ny as u32);
+                    if thin[(ny * w + nx) as usize] >= low && edges.get_pixel(nx, ny)[0] == 0 {
+                        edges.put_pixel(nx, ny, Luma([255]));
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+
+        DynamicImage::ImageLuma8(edges)
     }
 
-    fn convolution(&self, kernel: Kernel) -> DynamicImage {
+    fn gradient(
+        &self,
+        operator: GradientOperator,
+        output: GradientOutput,
+        threshold: f32,
+    ) -> DynamicImage {
         let (w, h) = self.dimensions();
-        // relative kernel weight position to each pixel
-        let k_pos = [
-            (-1, -1),
-            (0, -1),
-            (1, -1),
-            (-1, 0),
-            (0, 0),
-            (1, 0),
-            (-1, 1),
-            (0, 1),
-            (1, 1),
-        ];
-
-        let mut new_img = self.to_grayscale_rgb();
-        let clamp = |rgb: (f32, f32, f32)| {
-            if kernel == LAPLACIANO || kernel == GAUSSIANO || kernel == PASSA_ALTAS {
-                (
-                    min(255, max(rgb.0 as i32, 0)) as u8,
-                    min(255, max(rgb.1 as i32, 0)) as u8,
-                    min(255, max(rgb.2 as i32, 0)) as u8,
+        let (kernel_x, kernel_y) = operator.kernels();
+        let gradients = gradients(&luma_planes(self), w, h, kernel_x, kernel_y);
+        let scale = operator.scale();
+
+        // magnitude normalizada para que uma borda de 0 a 255 em um eixo resulte em 255
+        let magnitude = |(gx, gy): (f32, f32)| (gx.hypot(gy) / scale).min(255.0);
+
+        match output {
+            GradientOutput::Magnitude => DynamicImage::ImageLuma8(
+                GrayImage::from_raw(
+                    w,
+                    h,
+                    gradients
+                        .iter()
+                        .map(|g| magnitude(*g).round() as u8)
+                        .collect(),
                 )
-            } else {
-                (
-                    min(255, max(rgb.0 as i32 + 127, 0)) as u8,
-                    min(255, max(rgb.1 as i32 + 127, 0)) as u8,
-                    min(255, max(rgb.2 as i32 + 127, 0)) as u8,
+                .unwrap(),
+            ),
+            GradientOutput::Edges => DynamicImage::ImageLuma8(
+                GrayImage::from_raw(
+                    w,
+                    h,
+                    gradients
+                        .iter()
+                        .map(|g| if magnitude(*g) >= threshold { 255 } else { 0 })
+                        .collect(),
                 )
+                .unwrap(),
+            ),
+            GradientOutput::Orientation => {
+                let mut raw = Vec::with_capacity((w * h * 3) as usize);
+                for (gx, gy) in gradients.iter() {
+                    let angle = gy.atan2(*gx).to_degrees();
+                    let (r, g, b) = hsv_to_rgb(angle, 1.0, magnitude((*gx, *gy)) / 255.0);
+                    raw.extend_from_slice(&[r, g, b]);
+                }
+
+                DynamicImage::ImageRgb8(RgbImage::from_raw(w, h, raw).unwrap())
             }
-        };
+        }
+    }
+}
 
-        let mut apply_kernel = |x, y| {
-            let mut sum = (0.0, 0.0, 0.0);
+// Canais RGB em 0..255, sem arredondar imagens de 16 bits e HDR
+fn color_planes(image: &DynamicImage) -> Vec<[f32; 3]> {
+    if is_high_precision(image) {
+        return float_pixels(image)
+            .iter()
+            .map(|p| [p[0] * 255.0, p[1] * 255.0, p[2] * 255.0])
+            .collect();
+    }
 
-            for (idx, w) in kernel.iter().enumerate() {
-                let x = (x as i32 + k_pos[idx].0) as u32;
-                let y = (y as i32 + k_pos[idx].1) as u32;
+    image
+        .to_rgb8()
+        .pixels()
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect()
+}
 
-                let pixel = self.get_pixel(x, y);
+// Luminancia da imagem no primeiro canal de cada plano
+fn luma_planes(image: &DynamicImage) -> Vec<[f32; 3]> {
+    if is_high_precision(image) {
+        return float_pixels(image)
+            .iter()
+            .map(|p| [float_luma(p) * 255.0, 0.0, 0.0])
+            .collect();
+    }
 
-                sum.0 += (pixel[0] as f32) * w;
-                sum.1 += (pixel[1] as f32) * w;
-                sum.2 += (pixel[2] as f32) * w;
-            }
-            let sum = clamp(sum);
+    image
+        .to_grayscale()
+        .to_luma8()
+        .pixels()
+        .map(|p| [p[0] as f32, 0.0, 0.0])
+        .collect()
+}
 
-            new_img.put_pixel(x, y, Rgba::from([sum.0 as u8, sum.1 as u8, sum.2 as u8, 1]));
-        };
+// Derivadas (gx, gy) do primeiro canal de cada plano
+fn gradients(
+    planes: &[[f32; 3]],
+    w: u32,
+    h: u32,
+    kernel_x: [f32; 9],
+    kernel_y: [f32; 9],
+) -> Vec<(f32, f32)> {
+    let gx = convolve_planes(planes, w, h, &kernel_x.into(), BorderMode::Clamp, 1);
+    let gy = convolve_planes(planes, w, h, &kernel_y.into(), BorderMode::Clamp, 1);
+
+    gx.iter()
+        .zip(gy.iter())
+        .map(|(x, y)| (x[0], y[0]))
+        .collect()
+}
 
-        for (x, y, _) in self.pixels() {
-            if x == 0 || y == 0 || x == (w - 1) || y == (h - 1) {
-                continue;
+// Aplica o kernel aos primeiros `channels` canais de cada plano, sem limitar o resultado
+fn convolve_planes(
+    planes: &[[f32; 3]],
+    w: u32,
+    h: u32,
+    kernel: &Kernel,
+    border: BorderMode,
+    channels: usize,
+) -> Vec<[f32; 3]> {
+    let (kw, kh) = kernel.dimensions();
+    // raio do kernel em cada eixo, relativo ao pixel central
+    let (rx, ry) = ((kw / 2) as i64, (kh / 2) as i64);
+
+    // indice no plano com a politica de borda aplicada, None fora da imagem com Zero
+    let index = |x: i64, y: i64| match (border.index(x, w), border.index(y, h)) {
+        (Some(x), Some(y)) => Some((y * w + x) as usize),
+        _ => None,
+    };
+
+    let mut sums = vec![[0.0f32; 3]; (w * h) as usize];
+
+    match kernel {
+        Kernel::Dense { width, weights, .. } => {
+            for y in 0..h {
+                for x in 0..w {
+                    let sum = &mut sums[(y * w + x) as usize];
+
+                    for (idx, weight) in weights.iter().enumerate() {
+                        let kx = (idx % width) as i64;
+                        let ky = (idx / width) as i64;
+
+                        if let Some(i) = index(x as i64 + kx - rx, y as i64 + ky - ry) {
+                            for channel in 0..channels {
+                                sum[channel] += planes[i][channel] * weight;
+                            }
+                        }
+                    }
+                }
             }
-
-            apply_kernel(x, y);
         }
+        Kernel::Separable {
+            horizontal,
+            vertical,
+        } => {
+            // Primeira passada: kernel horizontal ao longo das linhas
+            let mut rows = vec![[0.0f32; 3]; (w * h) as usize];
+            for y in 0..h {
+                for x in 0..w {
+                    let sum = &mut rows[(y * w + x) as usize];
+
+                    for (k, weight) in horizontal.iter().enumerate() {
+                        if let Some(i) = index(x as i64 + k as i64 - rx, y as i64) {
+                            for channel in 0..channels {
+                                sum[channel] += planes[i][channel] * weight;
+                            }
+                        }
+                    }
+                }
+            }
 
-        new_img
+            // Segunda passada: kernel vertical sobre o resultado da primeira
+            for y in 0..h {
+                for x in 0..w {
+                    let sum = &mut sums[(y * w + x) as usize];
+
+                    for (k, weight) in vertical.iter().enumerate() {
+                        if let Some(i) = index(x as i64, y as i64 + k as i64 - ry) {
+                            for channel in 0..channels {
+                                sum[channel] += rows[i][channel] * weight;
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
+
+    sums
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::imageops2::ImageExt2;
+    use crate::imageops::{composite_checkerboard, ImageExt};
+    use crate::imageops2::{
+        cumulative_histogram, hsv_to_rgb, BorderMode, ConvolutionColor, EqualizeMode,
+        GradientOperator, GradientOutput, ImageExt2, Kernel, Normalization, ResizeFilter,
+        ToneCurve, GAUSSIANO,
+    };
+    use image::GenericImageView;
 
     use std::{error::Error, path::Path};
 
@@ -318,4 +1517,537 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_histogram_equalize() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        let equalized = img.histogram_equalize(EqualizeMode::Grayscale);
+        let histogram = equalized.grayscale_histogram();
+        let cumulative = cumulative_histogram(&histogram);
+
+        let (w, h) = img.dimensions();
+        assert_eq!(cumulative[255], w * h);
+
+        // o histograma acumulado da imagem equalizada deve ser aproximadamente linear
+        for (idx, count) in cumulative.iter().enumerate() {
+            let expected = (idx as f64 + 1.0) / 256.0;
+            let actual = *count as f64 / (w * h) as f64;
+            assert!(histogram[idx] == 0 || (expected - actual).abs() < 0.1);
+        }
+
+        for mode in [EqualizeMode::PerChannel, EqualizeMode::Luminance].iter() {
+            assert_eq!(img.histogram_equalize(*mode).dimensions(), (w, h));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_histogram_match() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+        let target = image::open(dbg!(image_folder_path.join("Underwater_53k.jpg")))?;
+
+        // casar uma imagem com ela mesma nao deve alterar os tons nem as cores
+        let matched = img.histogram_match(&img);
+        assert_eq!(matched.color(), img.color());
+        for (a, b) in matched.to_rgb8().pixels().zip(img.to_rgb8().pixels()) {
+            for channel in 0..3 {
+                assert!((a[channel] as i16 - b[channel] as i16).abs() <= 2);
+            }
+        }
+
+        let gray = img.to_grayscale();
+        assert_eq!(gray.histogram_match(&img).color(), gray.color());
+
+        let matched = img.histogram_match(&target);
+        let matched_cumulative = cumulative_histogram(&matched.grayscale_histogram());
+        let target_cumulative = cumulative_histogram(&target.grayscale_histogram());
+
+        let (w, h) = img.dimensions();
+        let (tw, th) = target.dimensions();
+        for idx in 0..256 {
+            let matched_freq = matched_cumulative[idx] as f64 / (w * h) as f64;
+            let target_freq = target_cumulative[idx] as f64 / (tw * th) as f64;
+            assert!((matched_freq - target_freq).abs() < 0.1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+        let (w, h) = img.dimensions();
+
+        let filters = [
+            ResizeFilter::Nearest,
+            ResizeFilter::Bilinear,
+            ResizeFilter::Bicubic,
+            ResizeFilter::Lanczos3,
+        ];
+
+        for filter in filters.iter() {
+            // fatores nao inteiros devem gerar exatamente as dimensoes pedidas
+            assert_eq!(
+                ImageExt2::resize(&img, 123, 77, *filter).dimensions(),
+                (123, 77)
+            );
+            assert_eq!(
+                ImageExt2::resize(&img, w * 3 / 2, h * 5 / 3, *filter).dimensions(),
+                (w * 3 / 2, h * 5 / 3)
+            );
+
+            // manter o tamanho nao deve alterar a imagem nem o seu tipo
+            assert_eq!(ImageExt2::resize(&img, w, h, *filter), img);
+            let resized = ImageExt2::resize(&img, w / 2, h / 2, *filter);
+            assert_eq!(resized.color(), image::ColorType::Rgb8);
+
+            // uma imagem constante deve continuar constante
+            let flat = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                7,
+                5,
+                image::Rgba([10, 200, 30, 255]),
+            ));
+            let resized = ImageExt2::resize(&flat, 17, 3, *filter);
+            for (_, _, pixel) in resized.pixels() {
+                assert_eq!(pixel, image::Rgba([10, 200, 30, 255]));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convolution_kernel_sizes() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        // kernels separaveis devem dar o mesmo resultado que o kernel denso equivalente
+        for kernel in [Kernel::gaussian(5, 1.0), Kernel::box_blur(7)].iter() {
+            let (kw, kh) = kernel.dimensions();
+            let dense = Kernel::new(kw, kh, kernel.weights());
+
+            let separable_result = img
+                .convolution(
+                    kernel,
+                    BorderMode::Clamp,
+                    ConvolutionColor::PerChannel,
+                    Normalization::Clamp,
+                )
+                .to_rgb8();
+            let dense_result = img
+                .convolution(
+                    &dense,
+                    BorderMode::Clamp,
+                    ConvolutionColor::PerChannel,
+                    Normalization::Clamp,
+                )
+                .to_rgb8();
+
+            for (a, b) in separable_result.iter().zip(dense_result.iter()) {
+                assert!((*a as i32 - *b as i32).abs() <= 1);
+            }
+        }
+
+        let gaussian = Kernel::gaussian(3, 0.85);
+        let gaussian_3x3 = Kernel::from(GAUSSIANO);
+        for (a, b) in gaussian.weights().iter().zip(gaussian_3x3.weights().iter()) {
+            assert!((a - b).abs() < 0.01);
+        }
+
+        let wide = Kernel::new(7, 3, vec![1.0 / 21.0; 21]);
+        assert_eq!(
+            img.convolution(
+                &wide,
+                BorderMode::Zero,
+                ConvolutionColor::Grayscale,
+                Normalization::Clamp
+            )
+            .dimensions(),
+            img.dimensions()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convolution_border_modes() {
+        let flat = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            6,
+            4,
+            image::Rgb([90, 150, 210]),
+        ));
+
+        for border in [BorderMode::Clamp, BorderMode::Reflect, BorderMode::Wrap].iter() {
+            for kernel in [Kernel::box_blur(5), Kernel::new(3, 3, vec![1.0 / 9.0; 9])].iter() {
+                let result = flat.convolution(
+                    kernel,
+                    *border,
+                    ConvolutionColor::PerChannel,
+                    Normalization::Clamp,
+                );
+                for (_, _, pixel) in result.pixels() {
+                    assert_eq!(pixel, image::Rgba([90, 150, 210, 255]));
+                }
+            }
+        }
+
+        // com borda zero os cantos recebem apenas parte do kernel
+        let result = flat.convolution(
+            &Kernel::box_blur(3),
+            BorderMode::Zero,
+            ConvolutionColor::PerChannel,
+            Normalization::Clamp,
+        );
+        assert_eq!(result.get_pixel(0, 0), image::Rgba([40, 67, 93, 255]));
+        assert_eq!(result.get_pixel(1, 1), image::Rgba([90, 150, 210, 255]));
+
+        assert_eq!(BorderMode::Reflect.index(-1, 4), Some(0));
+        assert_eq!(BorderMode::Reflect.index(-2, 4), Some(1));
+        assert_eq!(BorderMode::Reflect.index(5, 4), Some(2));
+        assert_eq!(BorderMode::Wrap.index(-1, 4), Some(3));
+        assert_eq!(BorderMode::Wrap.index(4, 4), Some(0));
+        assert_eq!(BorderMode::Clamp.index(9, 4), Some(3));
+        assert_eq!(BorderMode::Zero.index(-1, 4), None);
+    }
+
+    #[test]
+    fn test_convolution_color_modes() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Underwater_53k.jpg")))?;
+        let identity = Kernel::new(3, 3, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+
+        // o kernel identidade preserva a imagem em cada modo
+        let per_channel = img.convolution(
+            &identity,
+            BorderMode::Clamp,
+            ConvolutionColor::PerChannel,
+            Normalization::Clamp,
+        );
+        assert_eq!(per_channel.to_rgb8(), img.to_rgb8());
+
+        let grayscale = img.convolution(
+            &identity,
+            BorderMode::Clamp,
+            ConvolutionColor::Grayscale,
+            Normalization::Clamp,
+        );
+        assert_eq!(grayscale.to_rgb8(), img.to_grayscale_rgb().to_rgb8());
+
+        let luminance = img.convolution(
+            &identity,
+            BorderMode::Clamp,
+            ConvolutionColor::Luminance,
+            Normalization::Clamp,
+        );
+        for (a, b) in luminance.to_rgb8().iter().zip(img.to_rgb8().iter()) {
+            assert!((*a as i32 - *b as i32).abs() <= 2);
+        }
+
+        // o blur colorido nao deve perder a cor
+        let blurred = img.convolution(
+            &Kernel::gaussian(5, 1.0),
+            BorderMode::Clamp,
+            ConvolutionColor::Luminance,
+            Normalization::Clamp,
+        );
+        assert!(blurred
+            .pixels()
+            .any(|(_, _, p)| p[0] != p[1] || p[1] != p[2]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convolution_normalization() {
+        // degrau vertical: metade esquerda preta, metade direita branca
+        let step = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 4, |x, _| {
+            if x < 4 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([200, 200, 200])
+            }
+        }));
+        let kernel = Kernel::from([0.0, 0.0, 0.0, 0.0, 1.0, -1.0, 0.0, 0.0, 0.0]);
+        let convolve = |normalization| {
+            step.convolution(
+                &kernel,
+                BorderMode::Clamp,
+                ConvolutionColor::PerChannel,
+                normalization,
+            )
+        };
+
+        // a resposta na borda do degrau eh -200, nas regioes planas eh 0
+        assert_eq!(convolve(Normalization::Clamp).get_pixel(3, 0)[0], 0);
+        assert_eq!(convolve(Normalization::Offset127).get_pixel(3, 0)[0], 0);
+        assert_eq!(convolve(Normalization::Offset127).get_pixel(0, 0)[0], 127);
+        assert_eq!(convolve(Normalization::Absolute).get_pixel(3, 0)[0], 200);
+        assert_eq!(convolve(Normalization::MinMax).get_pixel(3, 0)[0], 0);
+        assert_eq!(convolve(Normalization::MinMax).get_pixel(0, 0)[0], 255);
+    }
+
+    #[test]
+    fn test_canny() -> Result<(), Box<dyn Error>> {
+        // quadrado claro sobre fundo escuro: a borda deve ser um contorno fino
+        let square = image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(32, 32, |x, y| {
+            if (8..24).contains(&x) && (8..24).contains(&y) {
+                image::Luma([200])
+            } else {
+                image::Luma([20])
+            }
+        }));
+
+        let edges = square.canny(20.0, 60.0, 1.0);
+        assert_eq!(edges.dimensions(), (32, 32));
+
+        let is_edge = |x, y| edges.get_pixel(x, y)[0] == 255;
+        assert!(!is_edge(2, 2));
+        assert!(!is_edge(16, 16));
+        assert!((6..=9).any(|x| is_edge(x, 16)));
+        assert!((22..=25).any(|x| is_edge(x, 16)));
+
+        // supressao de nao maximos deixa no maximo 2 pixels de espessura por lado
+        let row_edges = (0..16).filter(|x| is_edge(*x, 16)).count();
+        assert!((1..=2).contains(&row_edges));
+
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        // limiares maiores nunca produzem mais bordas
+        let count =
+            |img: &image::DynamicImage| img.to_luma8().iter().filter(|p| **p == 255).count();
+        assert!(count(&img.canny(50.0, 150.0, 1.4)) <= count(&img.canny(20.0, 60.0, 1.4)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gradient() {
+        // degrau vertical de 0 para 255: gradiente apenas em x
+        let step = image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(16, 16, |x, _| {
+            image::Luma([if x < 8 { 0 } else { 255 }])
+        }));
+
+        for operator in [
+            GradientOperator::Sobel,
+            GradientOperator::Prewitt,
+            GradientOperator::Scharr,
+        ]
+        .iter()
+        {
+            let magnitude = step.gradient(*operator, GradientOutput::Magnitude, 0.0);
+            assert_eq!(magnitude.get_pixel(2, 8)[0], 0);
+            // a conversao para cinza leva 255 para 254
+            assert!(magnitude.get_pixel(7, 8)[0] >= 254);
+            assert!(magnitude.get_pixel(8, 8)[0] >= 254);
+
+            let edges = step.gradient(*operator, GradientOutput::Edges, 128.0);
+            let row: Vec<u8> = (0..16).map(|x| edges.get_pixel(x, 8)[0]).collect();
+            assert_eq!(row.iter().filter(|p| **p == 255).count(), 2);
+        }
+
+        // direcao 0 graus (gradiente para a direita) e vermelha, fundo liso e preto
+        let orientation = step.gradient(GradientOperator::Sobel, GradientOutput::Orientation, 0.0);
+        assert!(orientation.get_pixel(8, 8)[0] >= 254);
+        assert_eq!(orientation.get_pixel(8, 8).0[1..3], [0, 0]);
+        assert_eq!(orientation.get_pixel(2, 8).0[..3], [0, 0, 0]);
+
+        assert_eq!(hsv_to_rgb(120.0, 1.0, 1.0), (0, 255, 0));
+        assert_eq!(hsv_to_rgb(-120.0, 1.0, 1.0), (0, 0, 255));
+    }
+
+    #[test]
+    fn test_brightness_contrast() {
+        let img = image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(16, 16, |x, y| {
+            image::Luma([(y * 16 + x) as u8])
+        }));
+        let values = |image: &image::DynamicImage| image.to_luma8().into_raw();
+
+        // brilho negativo escurece e satura em 0
+        let darker = values(&img.adjust_brightness(-100));
+        assert_eq!(darker[0], 0);
+        assert_eq!(darker[150], 50);
+        assert_eq!(darker[255], 155);
+        assert!(values(&img.adjust_brightness(-255)).iter().all(|v| *v == 0));
+        assert!(values(&img.adjust_brightness(255))
+            .iter()
+            .all(|v| *v == 255));
+        assert_eq!(img.adjust_brightness(0), img);
+
+        // ganhos fracionarios reduzem ou aumentam o contraste com arredondamento
+        let reduced = values(&img.adjust_contrast_2(0.5));
+        assert_eq!(reduced[101], 51);
+        assert_eq!(reduced[255], 128);
+        let increased = values(&img.adjust_contrast_2(1.5));
+        assert_eq!(increased[100], 150);
+        assert_eq!(increased[200], 255);
+        assert!(values(&img.adjust_contrast_2(0.0)).iter().all(|v| *v == 0));
+        assert_eq!(img.adjust_contrast_2(1.0), img);
+
+        // com 16 bits o deslocamento e relativo ao valor maximo do tipo
+        let deep = image::DynamicImage::ImageLuma16(img.to_luma16());
+        assert_eq!(deep.adjust_brightness(-100).to_luma8().into_raw(), darker);
+    }
+
+    #[test]
+    fn test_gamma_tone_curve() {
+        let img = image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(16, 16, |x, y| {
+            image::Luma([(y * 16 + x) as u8])
+        }));
+        let values = |image: &image::DynamicImage| image.to_luma8().into_raw();
+
+        // gamma acima de 1 clareia os tons medios e mantem as pontas
+        assert_eq!(img.gamma_correction(1.0), img);
+        let brighter = values(&img.gamma_correction(2.0));
+        assert_eq!((brighter[0], brighter[64], brighter[255]), (0, 128, 255));
+        let darker = values(&img.gamma_correction(0.5));
+        assert_eq!((darker[0], darker[128], darker[255]), (0, 64, 255));
+
+        // pontos colineares formam uma reta e a curva passa pelos pontos de controle
+        let line = ToneCurve::new(vec![(255.0, 0.0), (0.0, 255.0), (128.0, 127.0)]);
+        assert_eq!(img.tone_curve(&line), img.negative());
+        assert_eq!(img.tone_curve(&ToneCurve::identity()), img);
+
+        let s_curve = ToneCurve::new(vec![
+            (0.0, 0.0),
+            (64.0, 40.0),
+            (192.0, 215.0),
+            (255.0, 255.0),
+        ]);
+        let lut = s_curve.lut();
+        assert_eq!((lut[64], lut[192]), (40, 215));
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(values(&img.tone_curve(&s_curve)), lut.to_vec());
+
+        // fora dos pontos extremos a saida e constante
+        let clipped = ToneCurve::new(vec![(50.0, 20.0), (200.0, 230.0)]);
+        let lut = clipped.lut();
+        assert!(lut[..=50].iter().all(|v| *v == 20));
+        assert!(lut[200..].iter().all(|v| *v == 230));
+
+        // com 16 bits a curva e aplicada sem passar pela tabela de 8 bits
+        let deep = image::DynamicImage::ImageLuma16(img.to_luma16());
+        let curved = deep.tone_curve(&s_curve);
+        assert_eq!(curved.color(), deep.color());
+        assert_eq!(
+            curved.to_luma8().into_raw(),
+            values(&img.tone_curve(&s_curve))
+        );
+        assert_eq!(deep.gamma_correction(2.0).color(), deep.color());
+
+        // a curva e salva como a lista de pontos, que e validada ao carregar
+        let json = serde_json::to_string(&s_curve).unwrap();
+        assert_eq!(json, "[[0.0,0.0],[64.0,40.0],[192.0,215.0],[255.0,255.0]]");
+        assert_eq!(serde_json::from_str::<ToneCurve>(&json).unwrap(), s_curve);
+        let loaded: ToneCurve = serde_json::from_str("[[300.0,10.0],[0.0,0.0]]").unwrap();
+        assert_eq!(loaded.points(), &[(0.0, 0.0), (255.0, 10.0)]);
+        assert_eq!(
+            serde_json::from_str::<ToneCurve>("[]").unwrap(),
+            ToneCurve::identity()
+        );
+    }
+
+    #[test]
+    fn test_alpha_preserved() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        // metade esquerda transparente e alfa variando por linha na direita
+        let mut rgba = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?.to_rgba8();
+        for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+            pixel[3] = if x < 20 { 0 } else { (y * 7 % 256) as u8 };
+        }
+        let img = image::DynamicImage::ImageRgba8(rgba);
+        let (w, h) = img.dimensions();
+        let alpha = |image: &image::DynamicImage| -> Vec<u8> {
+            image.to_rgba8().pixels().map(|p| p[3]).collect()
+        };
+
+        // operacoes ponto a ponto mantem o alfa de cada pixel
+        let pointwise = vec![
+            img.to_grayscale(),
+            img.to_grayscale_rgb(),
+            img.quantize_grayscale(4),
+            img.histogram_equalize(EqualizeMode::Grayscale),
+            img.histogram_equalize(EqualizeMode::PerChannel),
+            img.histogram_equalize(EqualizeMode::Luminance),
+            img.histogram_match(&img),
+            img.adjust_brightness(40),
+            img.adjust_contrast_2(2.0),
+            img.negative(),
+            img.convolution(
+                &GAUSSIANO.into(),
+                BorderMode::Clamp,
+                ConvolutionColor::PerChannel,
+                Normalization::Clamp,
+            ),
+        ];
+        for result in pointwise.iter() {
+            assert!(result.color().has_alpha());
+            assert_eq!(alpha(result), alpha(&img));
+        }
+
+        assert_eq!(img.flip_v(), img.flipv());
+        assert_eq!(img.flip_h(), img.fliph());
+
+        // pixels transparentes nao contam no histograma
+        let total: u32 = img.grayscale_histogram().iter().sum();
+        let visible = alpha(&img).iter().filter(|a| **a > 0).count();
+        assert_eq!(total as usize, visible);
+
+        // a cor de pixels transparentes nao vaza nas medias
+        let mut pair = image::RgbaImage::new(2, 2);
+        pair.put_pixel(0, 0, image::Rgba([255, 0, 0, 0]));
+        pair.put_pixel(1, 0, image::Rgba([0, 0, 255, 255]));
+        pair.put_pixel(0, 1, image::Rgba([255, 0, 0, 0]));
+        pair.put_pixel(1, 1, image::Rgba([0, 0, 255, 255]));
+        let pair = image::DynamicImage::ImageRgba8(pair);
+        let resized = ImageExt2::resize(&pair, 3, 2, ResizeFilter::Bilinear);
+        assert_eq!(resized.get_pixel(1, 0).0[..3], [0, 0, 255]);
+
+        for result in [
+            ImageExt2::resize(&img, w / 2, h / 2, ResizeFilter::Bicubic),
+            ImageExt2::resize(&img, w * 2 - 1, h * 2 - 1, ResizeFilter::Bilinear),
+        ]
+        .iter()
+        {
+            assert!(result.color().has_alpha());
+        }
+
+        // a previa compoe o alfa sobre o xadrez
+        let preview = composite_checkerboard(&pair);
+        assert_eq!(preview.get_pixel(0, 0).0, [204, 204, 204]);
+        assert_eq!(preview.get_pixel(1, 0).0, [0, 0, 255]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_even_kernel_panics() {
+        Kernel::new(4, 4, vec![0.0; 16]);
+    }
 }
@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use druid::{
+    lens,
+    text::format::ParseFormatter,
+    widget::{Button, Flex, Label, RadioGroup, TextBox},
+    Data, Lens, LensExt, Widget, WidgetExt,
+};
+
+use crate::{
+    imageops::Operation,
+    imageops2::{Kernel, Normalization},
+    select_operation, AppState,
+};
+
+// Maior kernel que o editor suporta
+pub const MAX_KERNEL_SIZE: usize = 7;
+
+#[derive(Debug, Data, Clone, PartialEq)]
+pub struct KernelPreset {
+    pub name: String,
+    pub kernel: Kernel,
+    pub normalization: Normalization,
+}
+
+#[derive(Debug, Data, Clone, Lens)]
+pub struct KernelEditor {
+    pub size: usize,
+    // Pesos size x size em ordem de linhas
+    pub weights: Arc<Vec<f64>>,
+    pub normalization: Normalization,
+    pub preset_name: String,
+    pub presets: Arc<Vec<KernelPreset>>,
+}
+
+impl Default for KernelEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KernelEditor {
+    pub fn new() -> Self {
+        let mut weights = vec![0.0; 9];
+        weights[4] = 1.0;
+
+        Self {
+            size: 3,
+            weights: Arc::new(weights),
+            normalization: Normalization::Clamp,
+            preset_name: String::new(),
+            presets: Arc::new(vec![]),
+        }
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        Kernel::new(
+            self.size,
+            self.size,
+            self.weights.iter().map(|w| *w as f32).collect(),
+        )
+    }
+
+    // Muda o tamanho do kernel mantendo os pesos centralizados
+    pub fn resize(&mut self, size: usize) {
+        let mut weights = vec![0.0; size * size];
+        let old_size = self.size;
+
+        for y in 0..size {
+            for x in 0..size {
+                let old_x = x as i64 + (old_size as i64 - size as i64) / 2;
+                let old_y = y as i64 + (old_size as i64 - size as i64) / 2;
+
+                if (0..old_size as i64).contains(&old_x) && (0..old_size as i64).contains(&old_y) {
+                    weights[y * size + x] =
+                        self.weights[old_y as usize * old_size + old_x as usize];
+                }
+            }
+        }
+
+        self.size = size;
+        self.weights = Arc::new(weights);
+    }
+
+    // Divide os pesos pela soma, para que o kernel preserve o brilho medio
+    pub fn normalize(&mut self) {
+        let total: f64 = self.weights.iter().sum();
+        if total.abs() > f64::EPSILON {
+            self.weights = Arc::new(self.weights.iter().map(|w| w / total).collect());
+        }
+    }
+
+    pub fn save_preset(&mut self) {
+        let name = if self.preset_name.trim().is_empty() {
+            format!("Preset {}", self.presets.len() + 1)
+        } else {
+            self.preset_name.trim().to_owned()
+        };
+
+        let preset = KernelPreset {
+            name,
+            kernel: self.kernel(),
+            normalization: self.normalization,
+        };
+
+        Arc::make_mut(&mut self.presets).push(preset);
+        self.preset_name.clear();
+    }
+
+    pub fn load_preset(&mut self, preset: &KernelPreset) {
+        let (w, h) = preset.kernel.dimensions();
+        let size = w.max(h);
+        let mut weights = vec![0.0; size * size];
+
+        // kernels retangulares sao centralizados no editor quadrado
+        for (idx, weight) in preset.kernel.weights().iter().enumerate() {
+            let x = idx % w + (size - w) / 2;
+            let y = idx / w + (size - h) / 2;
+            weights[y * size + x] = *weight as f64;
+        }
+
+        self.size = size;
+        self.weights = Arc::new(weights);
+        self.normalization = preset.normalization;
+    }
+}
+
+pub fn build_kernel_editor(state: &AppState) -> impl Widget<AppState> {
+    let editor = &state.kernel_editor;
+    let mut col = Flex::column();
+
+    let mut size_row = Flex::row().with_child(Label::new("Kernel:"));
+    for size in (3..=MAX_KERNEL_SIZE).step_by(2) {
+        size_row.add_child(
+            Button::new(format!("{}x{}", size, size))
+                .on_click(move |_ctx, data: &mut AppState, _env| data.kernel_editor.resize(size)),
+        );
+    }
+    col.add_child(size_row);
+
+    for y in 0..editor.size {
+        let mut row = Flex::row();
+
+        for x in 0..editor.size {
+            let idx = y * editor.size + x;
+            let cell = TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .lens(lens::Map::new(
+                    move |data: &AppState| data.kernel_editor.weights[idx],
+                    move |data: &mut AppState, weight: f64| {
+                        Arc::make_mut(&mut data.kernel_editor.weights)[idx] = weight
+                    },
+                ))
+                .fix_width(60.0);
+
+            row.add_child(cell);
+        }
+
+        col.add_child(row);
+    }
+
+    col.add_child(
+        RadioGroup::new(vec![
+            ("Limitar", Normalization::Clamp),
+            ("+127", Normalization::Offset127),
+            ("Absoluto", Normalization::Absolute),
+            ("Min-Max", Normalization::MinMax),
+        ])
+        .lens(AppState::kernel_editor.then(KernelEditor::normalization)),
+    );
+
+    let action_row = Flex::row()
+        .with_child(
+            Button::new("Normalizar")
+                .on_click(|_ctx, data: &mut AppState, _env| data.kernel_editor.normalize()),
+        )
+        .with_child(
+            Button::new("Aplicar").on_click(|_ctx, data: &mut AppState, _env| {
+                let op = Operation::Convolution(
+                    data.kernel_editor.kernel(),
+                    data.kernel_editor.normalization,
+                );
+                select_operation(data, op);
+            }),
+        );
+    col.add_child(action_row);
+
+    let preset_row = Flex::row()
+        .with_child(
+            TextBox::new()
+                .with_placeholder("Nome do preset")
+                .lens(AppState::kernel_editor.then(KernelEditor::preset_name))
+                .fix_width(150.0),
+        )
+        .with_child(
+            Button::new("Salvar Preset")
+                .on_click(|_ctx, data: &mut AppState, _env| data.kernel_editor.save_preset()),
+        );
+    col.add_child(preset_row);
+
+    let mut presets_row = Flex::row();
+    for preset in editor.presets.iter() {
+        let preset = preset.clone();
+        presets_row.add_child(Button::new(preset.name.clone()).on_click(
+            move |_ctx, data: &mut AppState, _env| {
+                data.kernel_editor.load_preset(&preset);
+                let op = Operation::Convolution(preset.kernel.clone(), preset.normalization);
+                select_operation(data, op);
+            },
+        ));
+    }
+    col.add_child(presets_row);
+
+    col
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::imageops2::{Kernel, Normalization};
+    use crate::kernel_editor::KernelEditor;
+
+    #[test]
+    fn test_editor_kernel_roundtrip() {
+        let mut editor = KernelEditor::new();
+        assert_eq!(editor.kernel().dimensions(), (3, 3));
+
+        // aumentar e reduzir mantem o peso central
+        editor.resize(7);
+        assert_eq!(editor.kernel().dimensions(), (7, 7));
+        assert_eq!(editor.weights[3 * 7 + 3], 1.0);
+        editor.resize(3);
+        assert_eq!(
+            *editor.weights,
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]
+        );
+
+        editor.weights = std::sync::Arc::new(vec![1.0; 9]);
+        editor.normalize();
+        assert!(editor.weights.iter().all(|w| (w - 1.0 / 9.0).abs() < 1e-9));
+
+        editor.normalization = Normalization::MinMax;
+        editor.save_preset();
+        assert_eq!(editor.presets.len(), 1);
+        assert_eq!(editor.presets[0].name, "Preset 1");
+
+        let preset = editor.presets[0].clone();
+        let mut other = KernelEditor::new();
+        other.load_preset(&preset);
+        assert_eq!(other.kernel(), editor.kernel());
+        assert_eq!(other.normalization, Normalization::MinMax);
+
+        // kernels separaveis e retangulares tambem podem ser carregados
+        let mut preset = preset;
+        preset.kernel = Kernel::gaussian(5, 1.0);
+        other.load_preset(&preset);
+        assert_eq!(other.size, 5);
+    }
+}
@@ -1,77 +1,319 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use druid::{
-    widget::{Button, Flex, Label, Slider},
-    Color, Insets,
+    commands,
+    text::format::ParseFormatter,
+    widget::{Button, Checkbox, Flex, Label, RadioGroup, Slider, Spinner, TextBox},
+    AppDelegate, Color, Command, DelegateCtx, FileDialogOptions, FileSpec, Handled, HotKey, Insets,
+    SysMods, Target, WindowId,
 };
 use imageops::{ImageExt, Operation};
-use imageops2::ImageExt2;
+use imageops2::{
+    BorderMode, ConvolutionColor, EqualizeMode, GradientOperator, GradientOutput, ImageExt2,
+    Kernel, Normalization, ResizeFilter, GAUSSIANO, LAPLACIANO, PASSA_ALTAS, PREWITT_HX,
+    PREWITT_HY, SOBEL_HX, SOBEL_HY,
+};
 
 use crate::imageops::*;
+use cache::{RenderKey, RenderedImages};
+use curve_editor::{build_curve_editor, CurveEditor};
 use druid::{
     widget::SizedBox, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle,
     LifeCycleCtx, PaintCtx, Size, UpdateCtx, Widget, WidgetExt, WidgetId,
 };
+use export::{save_image, SaveFormat, SaveOptions};
+use file_drop::FILE_DROPPED;
+use history::{History, Step};
 use image::DynamicImage;
-
+use kernel_editor::{build_kernel_editor, KernelEditor};
+use pipeline::{build_pipeline_editor, Pipeline};
+use recipe::Recipe;
+use worker::{Worker, RENDER_DONE};
+
+pub mod batch;
+pub mod cache;
+pub mod cli;
+pub mod curve_editor;
+pub mod export;
+pub mod file_drop;
+pub mod history;
 pub mod imageops;
 pub mod imageops2;
+pub mod kernel_editor;
+pub mod pipeline;
+pub mod precision;
+pub mod recipe;
+pub mod worker;
 
 #[derive(Debug, Data, Clone, Lens)]
 pub struct AppState {
+    // Pasta listada na barra de imagens
+    pub image_folder: String,
+    // Caminhos completos da imagem aberta e da imagem alvo do casamento de histograma
     pub selected_image: Option<String>,
+    pub target_image: Option<String>,
+    // Caminho digitado ou colado para abrir; arquivos soltos sobre a janela tambem
+    // aparecem aqui (so no Linux, veja file_drop)
+    pub path_input: String,
     pub selected_operation: Operation,
     pub last_operation: Operation,
     pub param1: f64,
     pub param2: f64,
     pub param3: f64,
+    pub angle: f64,
+    pub expand_canvas: bool,
+    pub resize_filter: ResizeFilter,
+    // Tamanho final do Redimensionar; um lado em 0 segue a proporcao da imagem
+    pub resize_width: u32,
+    pub resize_height: u32,
+    pub border_mode: BorderMode,
+    pub convolution_color: ConvolutionColor,
+    pub gradient_operator: GradientOperator,
+    pub kernel_editor: KernelEditor,
+    pub curve_editor: CurveEditor,
+    pub history: History,
+    pub pipeline: Pipeline,
+    pub recipe_path: String,
+    pub save_format: SaveFormat,
+    pub jpeg_quality: f64,
+    // Grava a receita do resultado junto com a imagem salva
+    pub embed_recipe: bool,
+    // Mensagem exibida na interface, por exemplo erros ao carregar uma receita
+    pub status: String,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
+            image_folder: default_image_folder().to_string_lossy().into_owned(),
             selected_image: None,
+            target_image: None,
+            path_input: String::new(),
             selected_operation: Operation::FlipH,
             param1: 64.0,
             param2: 1.0,
             param3: 1.0,
+            angle: 0.0,
+            expand_canvas: true,
+            resize_filter: ResizeFilter::Bilinear,
+            resize_width: 0,
+            resize_height: 0,
+            border_mode: BorderMode::Clamp,
+            convolution_color: ConvolutionColor::Grayscale,
+            gradient_operator: GradientOperator::Sobel,
+            kernel_editor: KernelEditor::new(),
+            curve_editor: CurveEditor::new(),
+            history: History::new(),
+            pipeline: Pipeline::new(),
+            recipe_path: "receita.json".to_owned(),
+            save_format: SaveFormat::Png,
+            jpeg_quality: 90.0,
+            embed_recipe: false,
+            status: String::new(),
             last_operation: Operation::FlipH,
         }
     }
 }
 
+// Pasta de imagens do projeto se o programa rodar da raiz do repositorio, senao a pasta atual
+pub fn default_image_folder() -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let images = current_dir.join("src/images/1");
+
+    if images.is_dir() {
+        images
+    } else {
+        current_dir
+    }
+}
+
+pub const IMAGE_FILE_TYPES: FileSpec = FileSpec::new(
+    "Imagens",
+    &[
+        "png", "jpg", "jpeg", "bmp", "gif", "tif", "tiff", "webp", "ico", "tga", "pnm", "hdr",
+        "exr",
+    ],
+);
+
+// Abre uma imagem ou, se o caminho for uma pasta, passa a listar suas imagens
+pub fn open_path(data: &mut AppState, path: &Path) {
+    if path.is_dir() {
+        data.image_folder = path.to_string_lossy().into_owned();
+        data.status = format!("Pasta: {}", path.display());
+    } else if path.is_file() {
+        data.selected_image = Some(path.to_string_lossy().into_owned());
+        data.history.clear();
+        data.status = format!("Aberto: {}", path.display());
+    } else {
+        data.status = format!("Caminho nao encontrado: {}", path.display());
+    }
+}
+
+// Escolhe a imagem alvo do casamento de histograma, avisando em `status` se ela nao abre
+pub fn select_target(data: &mut AppState, path: &str) {
+    match image::image_dimensions(path) {
+        Ok(_) => {
+            data.target_image = Some(path.to_owned());
+            data.status = format!("Alvo: {}", path);
+        }
+        Err(err) => data.status = format!("Erro ao abrir o alvo {}: {}", path, err),
+    }
+}
+
 pub struct UiBuilder {
     inner: Box<dyn Widget<AppState>>,
+    // Processamento em segundo plano, criado quando o widget entra na janela
+    worker: Option<Worker>,
+    // Ultimo resultado entregue pelo worker, exibido ate o proximo ficar pronto
+    images: Option<Result<RenderedImages, String>>,
+    // Parametros do ultimo pedido enviado ao worker
+    key: Option<RenderKey>,
+    busy: bool,
+    // Caminho escolhido para salvar enquanto o resultado ainda estava sendo calculado
+    pending_save: Option<PathBuf>,
+}
+
+impl Default for UiBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UiBuilder {
     pub fn new() -> UiBuilder {
         UiBuilder {
             inner: Flex::column().boxed(),
+            worker: None,
+            images: None,
+            key: None,
+            busy: false,
+            pending_save: None,
         }
     }
 
     fn rebuild_inner(&mut self, data: &AppState) {
-        self.inner = build_app_ui(&data);
+        self.inner = build_app_ui(data, self.images.as_ref(), self.busy);
     }
+
+    // Envia um pedido ao worker se algo que afeta o processamento mudou
+    fn request_render(&mut self, data: &AppState) {
+        let key = RenderKey::from_state(data);
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+        self.key = Some(key);
+
+        if let Some(worker) = &self.worker {
+            worker.submit(data);
+            self.busy = data.selected_image.is_some();
+        }
+    }
+
+    fn save(&mut self, data: &mut AppState, path: &Path) {
+        data.status = match &self.images {
+            Some(Ok(images)) => save_result(data, path, &images.result.image),
+            Some(Err(err)) => err.clone(),
+            None => "Nenhuma imagem aberta".to_owned(),
+        };
+    }
+}
+
+// Se so mudaram campos editados em caixas de texto. Nesse caso a interface nao e
+// reconstruida, senao a caixa em edicao seria recriada e perderia o foco a cada tecla
+fn only_text_fields_changed(old_data: &AppState, data: &AppState) -> bool {
+    let mut data = data.clone();
+    data.kernel_editor.preset_name = old_data.kernel_editor.preset_name.clone();
+    data.kernel_editor.weights = old_data.kernel_editor.weights.clone();
+    data.recipe_path = old_data.recipe_path.clone();
+    data.path_input = old_data.path_input.clone();
+    data.resize_width = old_data.resize_width;
+    data.resize_height = old_data.resize_height;
+
+    old_data.same(&data)
 }
 
 impl Widget<AppState> for UiBuilder {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(output) = cmd.get(RENDER_DONE) {
+                let current = self
+                    .worker
+                    .as_ref()
+                    .is_some_and(|worker| worker.is_current(output.generation));
+
+                if current {
+                    self.images = output.images.clone();
+                    self.busy = false;
+
+                    if let Some(path) = self.pending_save.take() {
+                        self.save(data, &path);
+                    }
+
+                    self.rebuild_inner(data);
+                    ctx.children_changed();
+                }
+
+                ctx.set_handled();
+                return;
+            }
+
+            if let Some(path) = cmd.get(FILE_DROPPED) {
+                data.path_input = path.to_string_lossy().into_owned();
+                open_path(data, path);
+                ctx.set_handled();
+                return;
+            }
+
+            // Salvar usa o resultado ja calculado em vez de processar a imagem de novo
+            if let Some(file_info) = cmd.get(commands::SAVE_FILE_AS) {
+                if self.busy {
+                    self.pending_save = Some(file_info.path().to_path_buf());
+                    data.status = "Salvando quando o processamento terminar...".to_owned();
+                } else {
+                    self.save(data, file_info.path());
+                }
+
+                ctx.set_handled();
+                return;
+            }
+        }
+
+        if let Event::WindowConnected = event {
+            file_drop::accept_drops(ctx.get_external_handle());
+        }
+
         self.inner.event(ctx, event, data, env)
     }
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppState, env: &Env) {
         if let LifeCycle::WidgetAdded = event {
+            let sink = ctx.get_external_handle();
+            self.worker = Some(Worker::spawn(move |output| {
+                let _ = sink.submit_command(RENDER_DONE, output, Target::Auto);
+            }));
+
+            self.request_render(data);
             self.rebuild_inner(data);
         }
         self.inner.lifecycle(ctx, event, data, env)
     }
 
-    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
-        if !old_data.same(&data) {
-            self.rebuild_inner(data);
-            ctx.children_changed();
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, env: &Env) {
+        if !old_data.same(data) {
+            self.request_render(data);
+
+            if only_text_fields_changed(old_data, data) {
+                self.inner.update(ctx, old_data, data, env);
+            } else {
+                self.rebuild_inner(data);
+                ctx.children_changed();
+            }
         }
     }
 
@@ -94,21 +336,160 @@ impl Widget<AppState> for UiBuilder {
     }
 }
 
-fn build_operation_list() -> impl Widget<AppState> {
+pub fn select_operation(data: &mut AppState, op: Operation) {
+    // ao trocar para uma operacao com valor neutro, ela comeca sem alterar a imagem;
+    // os tres parametros sao trazidos para as faixas dos sliders da nova operacao,
+    // ja que um valor valido para uma operacao pode quebrar outra
+    if op != data.selected_operation {
+        if let Some(neutral) = neutral_param1(&op) {
+            data.param1 = neutral;
+        }
+    }
+    if op != Operation::None {
+        let [range1, range2, range3] = param_ranges(&op);
+        data.param1 = data.param1.clamp(range1.0, range1.1);
+        data.param2 = data.param2.clamp(range2.0, range2.1);
+        data.param3 = data.param3.clamp(range3.0, range3.1);
+    }
+
+    data.last_operation = data.selected_operation.clone();
+    data.selected_operation = op;
+}
+
+// Aplica a operacao selecionada sobre o resultado atual, adicionando-a ao historico
+pub fn push_operation(data: &mut AppState) {
+    let op = data.selected_operation.clone();
+    if op == Operation::None {
+        return;
+    }
+
+    let step = Step::from_state(op, data);
+    data.history.push(step);
+    select_operation(data, Operation::None);
+}
+
+pub fn undo(data: &mut AppState) {
+    if data.history.undo() {
+        select_operation(data, Operation::None);
+    }
+}
+
+pub fn redo(data: &mut AppState) {
+    if data.history.redo() {
+        select_operation(data, Operation::None);
+    }
+}
+
+// Atalhos de teclado, com Cmd no lugar de Ctrl no macOS: Ctrl+Z desfaz, Ctrl+Y ou
+// Ctrl+Shift+Z refaz e Ctrl+Enter aplica
+pub struct Delegate;
+
+impl AppDelegate<AppState> for Delegate {
+    fn event(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _window_id: WindowId,
+        event: Event,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Option<Event> {
+        if let Event::KeyDown(key_event) = &event {
+            if HotKey::new(SysMods::Cmd, "z").matches(key_event) {
+                undo(data);
+                return None;
+            }
+
+            if HotKey::new(SysMods::Cmd, "y").matches(key_event)
+                || HotKey::new(SysMods::CmdShift, "Z").matches(key_event)
+                || HotKey::new(SysMods::CmdShift, "z").matches(key_event)
+            {
+                redo(data);
+                return None;
+            }
+
+            if HotKey::new(SysMods::Cmd, druid::KbKey::Enter).matches(key_event) {
+                push_operation(data);
+                return None;
+            }
+        }
+
+        Some(event)
+    }
+
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(file_info) = cmd.get(commands::OPEN_FILE) {
+            open_path(data, file_info.path());
+            return Handled::Yes;
+        }
+
+        Handled::No
+    }
+}
+
+// Valor do param1 que deixa a imagem como esta
+pub(crate) fn neutral_param1(op: &Operation) -> Option<f64> {
+    match op {
+        Operation::AdjustBrightness => Some(0.0),
+        Operation::AdjustContrast | Operation::Gamma => Some(1.0),
+        _ => None,
+    }
+}
+
+// Fatores do zoom em param2 e param3. Valores menores que 1 ou nao finitos gerariam
+// imagens vazias ou gigantes, entao a operacao e ignorada
+fn zoom_factors(state: &AppState) -> Option<(f64, f64)> {
+    let valid = |factor: f64| factor.is_finite() && factor >= 1.0;
+
+    if valid(state.param2) && valid(state.param3) {
+        Some((state.param2, state.param3))
+    } else {
+        None
+    }
+}
+
+// Tamanho pedido para o Redimensionar; um lado em 0 segue a proporcao do outro
+fn resize_dimensions((w, h): (u32, u32), width: u32, height: u32) -> (u32, u32) {
+    let scaled = |side: u32, from: u32, to: u32| {
+        ((side as f64 * to as f64 / from.max(1) as f64).round() as u32).max(1)
+    };
+
+    match (width, height) {
+        (0, 0) => (w, h),
+        (0, height) => (scaled(w, h, height), height),
+        (width, 0) => (width, scaled(h, w, width)),
+        size => size,
+    }
+}
+
+// Faixas dos sliders param1, param2 e param3 para cada operacao
+pub(crate) fn param_ranges(op: &Operation) -> [(f64, f64); 3] {
+    match op {
+        Operation::Canny => [(0.0, 255.0), (0.0, 255.0), (0.5, 5.0)],
+        Operation::Gradient(_) => [(0.0, 255.0), (1.0, 5.0), (1.0, 5.0)],
+        Operation::AdjustBrightness => [(-255.0, 255.0), (1.0, 5.0), (1.0, 5.0)],
+        Operation::AdjustContrast => [(0.0, 5.0), (1.0, 5.0), (1.0, 5.0)],
+        Operation::Gamma => [(0.1, 5.0), (1.0, 5.0), (1.0, 5.0)],
+        _ => [(1.0, 255.0), (1.0, 5.0), (1.0, 5.0)],
+    }
+}
+
+fn build_operation_list(state: &AppState) -> impl Widget<AppState> {
     let mut col = Flex::column();
     let mut row = Flex::row();
     let mut row2 = Flex::row();
+    let mut row3 = Flex::row();
+    let mut row4 = Flex::row();
 
-    let build_op_btn = |text, op| {
-        Button::new(text).on_click(move |_ctx, data: &mut AppState, _env| {
-            if data.selected_operation != Operation::Save {
-                data.last_operation = data.selected_operation;
-            } else {
-                data.selected_operation = data.last_operation;
-            }
-
-            data.selected_operation = op;
-        })
+    let build_op_btn = |text, op: Operation| {
+        Button::new(text)
+            .on_click(move |_ctx, data: &mut AppState, _env| select_operation(data, op.clone()))
     };
 
     row.add_flex_child(build_op_btn("Limpar", Operation::None), 1.0);
@@ -116,89 +497,165 @@ fn build_operation_list() -> impl Widget<AppState> {
     row.add_flex_child(build_op_btn("Contraste", Operation::AdjustContrast), 1.0);
     row.add_flex_child(build_op_btn("Grayscale", Operation::Grayscale), 1.0);
     row.add_flex_child(build_op_btn("Brilho", Operation::AdjustBrightness), 1.0);
+    row.add_flex_child(build_op_btn("Gamma", Operation::Gamma), 1.0);
     row.add_flex_child(build_op_btn("Quantizar", Operation::Quantize), 1.0);
     row.add_flex_child(build_op_btn("ZoomOut", Operation::ZoomOut), 1.0);
     row.add_flex_child(build_op_btn("ZoomIn", Operation::ZoomIn), 1.0);
-    row.add_flex_child(build_op_btn("Salvar", Operation::Save), 1.0);
+
+    let build_conv_btn = |text, kernel: Kernel, normalization| {
+        build_op_btn(text, Operation::Convolution(kernel, normalization))
+    };
 
     row2.add_flex_child(
-        build_op_btn(
-            "Gaussiano",
-            Operation::Convolution([
-                0.0625, 0.125, 0.0625, 0.125, 0.25, 0.125, 0.0625, 0.125, 0.0625,
-            ]),
+        build_conv_btn("Gaussiano", GAUSSIANO.into(), Normalization::Clamp),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Laplaciano", LAPLACIANO.into(), Normalization::Clamp),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Passa Altas", PASSA_ALTAS.into(), Normalization::Clamp),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Prewitt Hx", PREWITT_HX.into(), Normalization::Offset127),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Prewitt Hy", PREWITT_HY.into(), Normalization::Offset127),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Sebel Hx", SOBEL_HX.into(), Normalization::Offset127),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn("Sebel Hy", SOBEL_HY.into(), Normalization::Offset127),
+        1.0,
+    );
+    row2.add_flex_child(
+        build_conv_btn(
+            "Gaussiano 5x5",
+            Kernel::gaussian(5, 1.0),
+            Normalization::Clamp,
         ),
         1.0,
     );
-
     row2.add_flex_child(
-        build_op_btn(
-            "Laplaciano",
-            Operation::Convolution([0.0, -1.0, 0.0, -1.0, 4.0, -1.0, 0.0, -1.0, 0.0]),
+        build_conv_btn(
+            "Gaussiano 7x7",
+            Kernel::gaussian(7, 1.5),
+            Normalization::Clamp,
         ),
         1.0,
     );
-
     row2.add_flex_child(
+        build_conv_btn("Media 9x9", Kernel::box_blur(9), Normalization::Clamp),
+        1.0,
+    );
+
+    row3.add_flex_child(
+        build_op_btn("Equalizar", Operation::Equalize(EqualizeMode::Grayscale)),
+        1.0,
+    );
+    row3.add_flex_child(
         build_op_btn(
-            "Passa Altas",
-            Operation::Convolution([-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0]),
+            "Equalizar RGB",
+            Operation::Equalize(EqualizeMode::PerChannel),
         ),
         1.0,
     );
-
-    row2.add_flex_child(
+    row3.add_flex_child(
         build_op_btn(
-            "Prewitt Hx",
-            Operation::Convolution([-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0]),
+            "Equalizar Luminancia",
+            Operation::Equalize(EqualizeMode::Luminance),
         ),
         1.0,
     );
+    row3.add_flex_child(
+        build_op_btn("Casar Histograma", Operation::HistogramMatch),
+        1.0,
+    );
+    row3.add_flex_child(
+        Label::new(|data: &AppState, _: &_| match &data.target_image {
+            Some(target) => format!(
+                "Alvo: {}",
+                Path::new(target)
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default()
+            ),
+            None => "Alvo: nenhum".to_owned(),
+        }),
+        1.0,
+    );
 
-    row2.add_flex_child(
+    row3.add_flex_child(build_op_btn("Canny", Operation::Canny), 1.0);
+
+    row4.add_flex_child(build_op_btn("Girar Horario", Operation::RotateCw), 1.0);
+    row4.add_flex_child(
+        build_op_btn("Girar Anti-horario", Operation::RotateCcw),
+        1.0,
+    );
+    row4.add_flex_child(
         build_op_btn(
-            "Prewitt Hy",
-            Operation::Convolution([-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0]),
+            "Rotacionar (Vizinho)",
+            Operation::Rotate(Interpolation::Nearest),
         ),
         1.0,
     );
-
-    row2.add_flex_child(
+    row4.add_flex_child(
         build_op_btn(
-            "Sebel Hx",
-            Operation::Convolution([-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0]),
+            "Rotacionar (Bilinear)",
+            Operation::Rotate(Interpolation::Bilinear),
         ),
         1.0,
     );
-    row2.add_flex_child(
+    row4.add_flex_child(
         build_op_btn(
-            "Sebel Hy",
-            Operation::Convolution([-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0]),
+            "Rotacionar (Bicubico)",
+            Operation::Rotate(Interpolation::Bicubic),
         ),
         1.0,
     );
+    row4.add_flex_child(Checkbox::new("Expandir").lens(AppState::expand_canvas), 1.0);
 
-    let mut param_row_1 = Flex::row();
-    let param_slider = Flex::column()
+    let history_row = Flex::row()
         .with_flex_child(
-            Slider::new().with_range(1.0, 255.0).fix_size(768.0, 50.0),
+            Button::new("Aplicar (Ctrl+Enter)")
+                .on_click(|_ctx, data: &mut AppState, _env| push_operation(data)),
             1.0,
         )
-        .lens(AppState::param1);
-
-    param_row_1.add_flex_child(Flex::column().with_flex_child(param_slider, 1.0), 1.0);
-    param_row_1.add_flex_child(
-        Flex::column().with_flex_child(
-            Label::new(|data: &AppState, _: &_| format!("{}", data.param1 as usize)),
+        .with_flex_child(
+            Button::new("Desfazer (Ctrl+Z)").on_click(|_ctx, data: &mut AppState, _env| undo(data)),
             1.0,
-        ),
-        1.0,
-    );
+        )
+        .with_flex_child(
+            Button::new("Refazer (Ctrl+Y)").on_click(|_ctx, data: &mut AppState, _env| redo(data)),
+            1.0,
+        )
+        .with_flex_child(
+            Label::new(|data: &AppState, _: &_| {
+                let applied: Vec<String> = data
+                    .history
+                    .applied()
+                    .iter()
+                    .map(|step| format!("{:?}", step.operation))
+                    .collect();
+                format!("Historico: {}", applied.join(" > "))
+            }),
+            3.0,
+        );
+
+    let ranges = param_ranges(&state.selected_operation);
 
     let mut param_row_1 = Flex::row();
     let param_slider = Flex::column()
         .with_flex_child(
-            Slider::new().with_range(1.0, 255.0).fix_size(768.0, 50.0),
+            Slider::new()
+                .with_range(ranges[0].0, ranges[0].1)
+                .fix_size(768.0, 50.0),
             1.0,
         )
         .lens(AppState::param1);
@@ -206,7 +663,11 @@ fn build_operation_list() -> impl Widget<AppState> {
     param_row_1.add_flex_child(Flex::column().with_flex_child(param_slider, 1.0), 1.0);
     param_row_1.add_flex_child(
         Flex::column().with_flex_child(
-            Label::new(|data: &AppState, _: &_| format!("{}", data.param1 as usize)),
+            Label::new(|data: &AppState, _: &_| match data.selected_operation {
+                Operation::AdjustBrightness => format!("{}", data.param1.round() as i16),
+                Operation::AdjustContrast | Operation::Gamma => format!("{:.2}", data.param1),
+                _ => format!("{}", data.param1 as usize),
+            }),
             1.0,
         ),
         1.0,
@@ -215,7 +676,9 @@ fn build_operation_list() -> impl Widget<AppState> {
     let mut param_row_2 = Flex::row();
     let param_slider = Flex::column()
         .with_flex_child(
-            Slider::new().with_range(1.0, 5.0).fix_size(512.0, 50.0),
+            Slider::new()
+                .with_range(ranges[1].0, ranges[1].1)
+                .fix_size(512.0, 50.0),
             1.0,
         )
         .lens(AppState::param2);
@@ -223,7 +686,7 @@ fn build_operation_list() -> impl Widget<AppState> {
     param_row_2.add_flex_child(Flex::column().with_flex_child(param_slider, 1.0), 1.0);
     param_row_2.add_flex_child(
         Flex::column().with_flex_child(
-            Label::new(|data: &AppState, _: &_| format!("{:.0}", data.param2)),
+            Label::new(|data: &AppState, _: &_| format!("{:.2}", data.param2)),
             1.0,
         ),
         1.0,
@@ -232,7 +695,9 @@ fn build_operation_list() -> impl Widget<AppState> {
     let mut param_row_3 = Flex::row();
     let param_slider = Flex::column()
         .with_flex_child(
-            Slider::new().with_range(1.0, 5.0).fix_size(512.0, 50.0),
+            Slider::new()
+                .with_range(ranges[2].0, ranges[2].1)
+                .fix_size(512.0, 50.0),
             1.0,
         )
         .lens(AppState::param3);
@@ -240,7 +705,104 @@ fn build_operation_list() -> impl Widget<AppState> {
     param_row_3.add_flex_child(Flex::column().with_flex_child(param_slider, 1.0), 1.0);
     param_row_3.add_flex_child(
         Flex::column().with_flex_child(
-            Label::new(|data: &AppState, _: &_| format!("{:.0}", data.param3)),
+            Label::new(|data: &AppState, _: &_| format!("{:.2}", data.param3)),
+            1.0,
+        ),
+        1.0,
+    );
+
+    let gradient_row = Flex::row()
+        .with_child(Label::new("Gradiente:"))
+        .with_flex_child(
+            RadioGroup::new(vec![
+                ("Sobel", GradientOperator::Sobel),
+                ("Prewitt", GradientOperator::Prewitt),
+                ("Scharr", GradientOperator::Scharr),
+            ])
+            .lens(AppState::gradient_operator),
+            1.0,
+        )
+        .with_flex_child(
+            build_op_btn("Magnitude", Operation::Gradient(GradientOutput::Magnitude)),
+            1.0,
+        )
+        .with_flex_child(
+            build_op_btn("Bordas", Operation::Gradient(GradientOutput::Edges)),
+            1.0,
+        )
+        .with_flex_child(
+            build_op_btn(
+                "Orientacao",
+                Operation::Gradient(GradientOutput::Orientation),
+            ),
+            1.0,
+        );
+
+    let border_row = Flex::row()
+        .with_child(Label::new("Borda da Convolucao:"))
+        .with_flex_child(
+            RadioGroup::new(vec![
+                ("Zero", BorderMode::Zero),
+                ("Replicar", BorderMode::Clamp),
+                ("Espelhar", BorderMode::Reflect),
+                ("Circular", BorderMode::Wrap),
+            ])
+            .lens(AppState::border_mode),
+            1.0,
+        )
+        .with_child(Label::new("Cor:"))
+        .with_flex_child(
+            RadioGroup::new(vec![
+                ("Cinza", ConvolutionColor::Grayscale),
+                ("RGB", ConvolutionColor::PerChannel),
+                ("Luminancia", ConvolutionColor::Luminance),
+            ])
+            .lens(AppState::convolution_color),
+            1.0,
+        );
+
+    let filter_row = Flex::row()
+        .with_child(Label::new("Filtro do Zoom:"))
+        .with_flex_child(
+            RadioGroup::new(vec![
+                ("Vizinho", ResizeFilter::Nearest),
+                ("Bilinear", ResizeFilter::Bilinear),
+                ("Bicubico", ResizeFilter::Bicubic),
+                ("Lanczos", ResizeFilter::Lanczos3),
+            ])
+            .lens(AppState::resize_filter),
+            1.0,
+        )
+        .with_child(Label::new("Largura:"))
+        .with_child(
+            TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .lens(AppState::resize_width)
+                .fix_width(60.0),
+        )
+        .with_child(Label::new("Altura:"))
+        .with_child(
+            TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .lens(AppState::resize_height)
+                .fix_width(60.0),
+        )
+        .with_child(build_op_btn("Redimensionar", Operation::Resize));
+
+    let mut angle_row = Flex::row();
+    let angle_slider = Flex::column()
+        .with_flex_child(
+            Slider::new()
+                .with_range(-180.0, 180.0)
+                .fix_size(768.0, 50.0),
+            1.0,
+        )
+        .lens(AppState::angle);
+
+    angle_row.add_flex_child(Flex::column().with_flex_child(angle_slider, 1.0), 1.0);
+    angle_row.add_flex_child(
+        Flex::column().with_flex_child(
+            Label::new(|data: &AppState, _: &_| format!("{:.0} graus", data.angle)),
             1.0,
         ),
         1.0,
@@ -248,234 +810,406 @@ fn build_operation_list() -> impl Widget<AppState> {
 
     col.add_flex_child(row, 2.0);
     col.add_default_spacer();
+    col.add_flex_child(history_row, 1.0);
+    col.add_default_spacer();
     col.add_flex_child(row2, 2.0);
     col.add_default_spacer();
+    col.add_flex_child(border_row, 1.0);
+    col.add_default_spacer();
+    col.add_flex_child(gradient_row, 1.0);
+    col.add_default_spacer();
+    col.add_flex_child(row3, 2.0);
+    col.add_default_spacer();
+    col.add_flex_child(row4, 2.0);
+    col.add_default_spacer();
     col.add_flex_child(param_row_1, 1.0);
     col.add_default_spacer();
     col.add_flex_child(param_row_2, 1.0);
     col.add_default_spacer();
     col.add_flex_child(param_row_3, 1.0);
+    col.add_default_spacer();
+    col.add_flex_child(filter_row, 1.0);
+    col.add_default_spacer();
+    col.add_flex_child(angle_row, 1.0);
 
     col
 }
 
-fn build_image_list() -> impl Widget<AppState> {
-    let image_folder_path = Path::new(&std::env::current_dir().unwrap())
-        .to_path_buf()
-        .join("src/images/1/");
+fn build_file_row() -> impl Widget<AppState> {
+    Flex::row()
+        .with_child(
+            Button::new("Abrir Imagem").on_click(|ctx, _data: &mut AppState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![IMAGE_FILE_TYPES])
+                    .default_type(IMAGE_FILE_TYPES);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            }),
+        )
+        .with_child(
+            Button::new("Escolher Pasta").on_click(|ctx, _data: &mut AppState, _env| {
+                let options = FileDialogOptions::new().select_directories();
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            }),
+        )
+        .with_flex_child(
+            TextBox::new()
+                .with_placeholder("Cole o caminho de uma imagem ou pasta")
+                .lens(AppState::path_input)
+                .expand_width(),
+            1.0,
+        )
+        .with_child(
+            Button::new("Abrir").on_click(|_ctx, data: &mut AppState, _env| {
+                let path = PathBuf::from(data.path_input.trim());
+                open_path(data, &path);
+            }),
+        )
+        .with_child(Label::new(|data: &AppState, _: &_| {
+            format!("Pasta: {}", data.image_folder)
+        }))
+}
 
-    let mut row = Flex::row();
-    for image_path in std::fs::read_dir(image_folder_path).unwrap() {
-        let image_path = image_path.unwrap();
+fn build_save_row() -> impl Widget<AppState> {
+    let formats = SaveFormat::supported()
+        .into_iter()
+        .map(|format| (format.name(), format))
+        .collect::<Vec<_>>();
+
+    Flex::row()
+        .with_child(
+            Button::new("Salvar Como").on_click(|ctx, data: &mut AppState, _env| {
+                let spec = data.save_format.file_spec();
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![spec])
+                    .default_type(spec)
+                    .default_name(default_save_name(data));
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            }),
+        )
+        .with_child(RadioGroup::new(formats).lens(AppState::save_format))
+        .with_child(Label::new(|data: &AppState, _: &_| {
+            format!("Qualidade JPEG: {:.0}", data.jpeg_quality)
+        }))
+        .with_child(
+            Slider::new()
+                .with_range(1.0, 100.0)
+                .lens(AppState::jpeg_quality)
+                .fix_width(200.0),
+        )
+        .with_child(Checkbox::new("Incluir receita").lens(AppState::embed_recipe))
+        .with_flex_child(
+            Label::new(|data: &AppState, _: &_| data.status.clone()),
+            1.0,
+        )
+}
 
-        let path_str = image_path.file_name().to_str().unwrap().to_owned();
+fn build_image_list(state: &AppState) -> Box<dyn Widget<AppState>> {
+    let images = match batch::list_images(Path::new(&state.image_folder)) {
+        Ok(images) => images,
+        Err(err) => {
+            return Label::new(format!(
+                "Nao foi possivel ler a pasta {}: {}",
+                state.image_folder, err
+            ))
+            .boxed()
+        }
+    };
 
-        let label = path_str.clone();
-        let btn = Button::new(label.clone())
+    let mut row = Flex::row();
+    for image_path in images {
+        let path_str = image_path.to_string_lossy().into_owned();
+        let label = image_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path_str.clone());
+
+        let path = path_str.clone();
+        let btn = Button::new(label)
             .on_click(move |_ctx, data: &mut AppState, _env| {
-                data.selected_image = label.clone().into()
+                data.selected_image = path.clone().into();
+                data.history.clear();
             })
             .fix_height(50.0);
 
+        let path = path_str.clone();
+        let target_btn = Button::new("Alvo")
+            .on_click(move |_ctx, data: &mut AppState, _env| select_target(data, &path))
+            .fix_height(25.0);
+
         let mut inner_col = Flex::column();
         inner_col.add_flex_child(btn, 1.0);
+        inner_col.add_flex_child(target_btn, 1.0);
         row.add_flex_child(inner_col, 1.0);
     }
 
-    row
+    row.boxed()
 }
 
-pub fn build_app_ui(state: &AppState) -> Box<dyn Widget<AppState>> {
+pub fn build_app_ui(
+    state: &AppState,
+    images: Option<&Result<RenderedImages, String>>,
+    busy: bool,
+) -> Box<dyn Widget<AppState>> {
     let mut col = Flex::column();
 
     col.set_main_axis_alignment(druid::widget::MainAxisAlignment::Start);
 
     let mut image_row = Flex::row();
-    let image_folder_path = Path::new(&std::env::current_dir().unwrap())
-        .to_path_buf()
-        .join("src/images/1/");
 
     let mut histogram_row = Flex::row();
-    if let Some(image_path) = state.selected_image.clone() {
-        let selected_image = image::open(image_folder_path.join(image_path)).unwrap();
-
-        let (width, height) = selected_image.get_dimensions();
-
-        let make_sized = |inner: &DynamicImage| {
-            SizedBox::new(
-                inner
-                    .to_druid_image()
-                    .fill_mode(druid::widget::FillStrat::Cover),
-            )
-            .fix_width(width as f64 * 1.2)
-            .fix_height(height as f64 * 1.2)
-            .border(Color::grey(0.6), 2.0)
-            .padding(Insets::uniform(10.0))
-        };
 
-        let original_image = make_sized(&selected_image);
+    if let Some(Err(err)) = &images {
+        image_row.add_flex_child(Label::new(err.clone()), 1.0);
+    }
 
-        image_row.add_flex_child(original_image, 1.0);
+    // o resultado anterior continua na tela enquanto o novo e calculado
+    let mut busy_row = Flex::row();
+    if busy {
+        busy_row.add_child(Spinner::new());
+        busy_row.add_spacer(8.0);
+        busy_row.add_child(Label::new("Processando..."));
+    }
 
-        image_row.add_flex_child(exec_op(&selected_image, state), 1.0);
+    if let Some(Ok(images)) = images {
+        let (width, height) = images.original.image.get_dimensions();
 
-        let mut hist_col = Flex::column();
+        image_row.add_flex_child(build_preview(&images.original.image, width, height), 1.0);
 
-        hist_col.add_flex_child(
-            build_histogram_label(&apply_operation(&selected_image, Operation::None, state)),
-            1.0,
-        );
-        hist_col.add_flex_child(
-            build_histogram(&selected_image, Operation::None, state),
-            6.0,
-        );
-        histogram_row.add_flex_child(hist_col, 1.0);
+        // o resultado ocupa o espaco da imagem sobre a qual a operacao foi aplicada
+        let (width, height) = images.current.get_dimensions();
+        image_row.add_flex_child(build_preview(&images.result.image, width, height), 1.0);
 
-        let mut hist_col = Flex::column();
+        for rendered in [&images.original, &images.result].iter() {
+            let mut hist_col = Flex::column();
 
-        hist_col.add_flex_child(
-            build_histogram_label(&apply_operation(
-                &selected_image,
-                state.selected_operation,
-                state,
-            )),
-            1.0,
-        );
-        hist_col.add_flex_child(
-            build_histogram(&selected_image, state.selected_operation, state),
-            6.0,
-        );
-        histogram_row.add_flex_child(hist_col, 1.0);
+            hist_col.add_flex_child(build_histogram_label(rendered.max_count), 1.0);
+            hist_col.add_flex_child(build_histogram(&rendered.histogram), 6.0);
+            histogram_row.add_flex_child(hist_col, 1.0);
+        }
     };
-
-    col.add_flex_child(build_image_list(), 1.0);
-    col.add_flex_child(build_operation_list(), 1.5);
+    histogram_row.add_flex_child(build_curve_editor(), 1.0);
+
+    col.add_child(build_file_row());
+    col.add_child(build_save_row());
+    col.add_flex_child(build_image_list(state), 1.0);
+    col.add_flex_child(
+        Flex::row()
+            .with_flex_child(build_operation_list(state), 3.0)
+            .with_flex_child(build_kernel_editor(state), 1.0)
+            .with_flex_child(build_pipeline_editor(state), 1.0),
+        1.5,
+    );
+    col.add_child(busy_row.fix_height(24.0));
     col.add_flex_child(image_row, 4.0);
     col.add_flex_child(histogram_row, 2.5);
     col.boxed()
 }
 
-pub fn build_histogram_label(image: &DynamicImage) -> impl Widget<AppState> {
-    let grayscale = image.to_grayscale();
-    let mut histogram: [u32; 256] = [0; 256];
-
-    for l in grayscale.as_bytes() {
-        histogram[*l as usize] += 1;
-    }
-
+pub fn build_histogram_label(max_val: u32) -> impl Widget<AppState> {
     // maximum value is going to be our full column
-    let max_val = histogram.iter().max().unwrap();
-    let pixel_value = (*max_val as f64) / 255.0;
+    let pixel_value = (max_val as f64) / 255.0;
 
     let label = Label::new(format!("1 pixel = {:.2}. Max = {}", pixel_value, max_val));
     label
 }
 
-pub fn build_histogram(
-    image: &DynamicImage,
-    op: Operation,
-    state: &AppState,
-) -> impl Widget<AppState> {
-    let build_image = || {
-        SizedBox::new(
-            apply_operation(image, op, &state)
-                .render_grayscale_histogram()
-                .to_druid_image()
-                .fill_mode(druid::widget::FillStrat::Fill),
-        )
-        .fix_width(256.0)
-        .fix_height(256.0)
-        .border(Color::grey(0.6), 2.0)
-        .padding(Insets::uniform(10.0))
-    };
-
-    build_image()
+pub fn build_histogram(histogram: &DynamicImage) -> impl Widget<AppState> {
+    SizedBox::new(
+        histogram
+            .to_druid_image()
+            .fill_mode(druid::widget::FillStrat::Fill),
+    )
+    .fix_width(256.0)
+    .fix_height(256.0)
+    .border(Color::grey(0.6), 2.0)
+    .padding(Insets::uniform(10.0))
 }
 
-pub fn exec_op(image: &DynamicImage, state: &AppState) -> impl Widget<AppState> {
-    let (width, height) = image.get_dimensions();
+pub fn build_preview(image: &DynamicImage, width: u32, height: u32) -> impl Widget<AppState> {
+    SizedBox::new(
+        image
+            .to_druid_image()
+            .fill_mode(druid::widget::FillStrat::Cover),
+    )
+    .fix_width(width as f64 * 1.2)
+    .fix_height(height as f64 * 1.2)
+    .border(Color::grey(0.6), 2.0)
+    .padding(Insets::uniform(10.0))
+}
 
-    let build_image = |op: Operation, state: &AppState| {
-        SizedBox::new(
-            apply_operation(image, op, &state)
-                .to_druid_image()
-                .fill_mode(druid::widget::FillStrat::Cover),
-        )
-        .fix_width(width as f64 * 1.2)
-        .fix_height(height as f64 * 1.2)
-        .border(Color::grey(0.6), 2.0)
-        .padding(Insets::uniform(10.0))
+// Nome curto da operacao, usado no nome padrao do arquivo salvo
+pub fn operation_slug(op: &Operation, state: &AppState) -> String {
+    let slug = match op {
+        Operation::None => "original".to_owned(),
+        Operation::FlipH => "flip_h".to_owned(),
+        Operation::FlipV => "flip_v".to_owned(),
+        Operation::Negative => "negative".to_owned(),
+        Operation::Grayscale => "grayscale".to_owned(),
+        Operation::Quantize => format!("quantize-{}", state.param1 as u8),
+        Operation::AdjustBrightness => {
+            format!("brightness-{}", state.param1.round() as i16)
+        }
+        Operation::AdjustContrast => format!("constrast-{:.2}", state.param1),
+        Operation::Gamma => format!("gamma-{:.2}", state.param1),
+        Operation::ToneCurve(_) => "tone-curve".to_owned(),
+        Operation::ZoomOut => format!(
+            "zoomout-{:.2}-{:.2}-{:?}",
+            state.param2, state.param3, state.resize_filter
+        ),
+        Operation::ZoomIn => format!(
+            "zoomin-{:.2}-{:.2}-{:?}",
+            state.param2, state.param3, state.resize_filter
+        ),
+        Operation::Resize => format!(
+            "resize-{}x{}-{:?}",
+            state.resize_width, state.resize_height, state.resize_filter
+        ),
+        Operation::Convolution(_, normalization) => format!("convolution-{:?}", normalization),
+        Operation::Equalize(mode) => format!("equalize-{:?}", mode),
+        Operation::HistogramMatch => "histogram-match".to_owned(),
+        Operation::RotateCw => "rotate_cw".to_owned(),
+        Operation::RotateCcw => "rotate_ccw".to_owned(),
+        Operation::Rotate(interpolation) => {
+            format!("rotate-{:.0}-{:?}", state.angle, interpolation)
+        }
+        Operation::Canny => format!(
+            "canny-{}-{}-{:.2}",
+            state.param1 as u8, state.param2 as u8, state.param3
+        ),
+        Operation::Gradient(output) => format!(
+            "gradient-{:?}-{:?}-{}",
+            state.gradient_operator, output, state.param1 as u8
+        ),
+        Operation::Pipeline(_) => "pipeline".to_owned(),
     };
 
-    if state.selected_operation == Operation::Save {
-        let image_to_save = apply_operation(image, state.last_operation, state);
+    slug.to_lowercase()
+}
 
-        let result_path = Path::new(&std::env::current_dir().unwrap())
-            .to_path_buf()
-            .join("src/result_images");
-
-        let selected = state.selected_image.clone().unwrap();
-        let image_name = Path::new(&selected).file_name().unwrap().to_str().unwrap();
-        let format_save = |op| result_path.join(format!("{}-{}.jpg", op, image_name));
-
-        match state.last_operation {
-            Operation::FlipH => image_to_save.save(format_save("flip_h")).unwrap(),
-            Operation::Negative => image_to_save.save(format_save("negative")).unwrap(),
-            Operation::FlipV => image_to_save.save(format_save("flip_v")).unwrap(),
-            Operation::Save => (),
-            Operation::Grayscale => image_to_save.save(format_save("grayscale")).unwrap(),
-            Operation::Quantize => image_to_save
-                .save(format_save(&format!("quantize-{}", state.param1 as u8)))
-                .unwrap(),
-            Operation::AdjustBrightness => image_to_save
-                .save(format_save(&format!("brightness-{}", state.param1 as u8)))
-                .unwrap(),
-
-            Operation::AdjustContrast => image_to_save
-                .save(format_save(&format!("constrast-{}", state.param1 as u8)))
-                .unwrap(),
-            Operation::ZoomOut => image_to_save
-                .save(format_save(&format!(
-                    "zoomout-{}-{}",
-                    state.param2.ceil() as u8,
-                    state.param3.ceil() as u8
-                )))
-                .unwrap(),
-            Operation::ZoomIn => image_to_save
-                .save(format_save(&format!(
-                    "zoomin-{}-{}",
-                    state.param2.ceil() as u8,
-                    state.param3.ceil() as u8
-                )))
-                .unwrap(),
-            Operation::Convolution(_) => image_to_save
-                .save(format_save(&format!("convolution",)))
-                .unwrap(),
-
-            _ => (),
-        };
+// Receita com os passos do historico seguidos da operacao selecionada
+pub fn current_recipe(state: &AppState) -> Recipe {
+    let mut pipeline = Pipeline::new();
+    for step in state.history.applied() {
+        pipeline.push(step.clone());
+    }
+
+    if state.selected_operation != Operation::None {
+        pipeline.push(Step::from_state(state.selected_operation.clone(), state));
     }
-    build_image(state.selected_operation, state)
+
+    Recipe::new(pipeline)
+}
+
+pub fn default_save_name(state: &AppState) -> String {
+    let stem = state
+        .selected_image
+        .as_ref()
+        .and_then(|path| Path::new(path).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "imagem".to_owned());
+
+    format!(
+        "{}-{}.{}",
+        operation_slug(&state.selected_operation, state),
+        stem,
+        state.save_format.extension()
+    )
 }
 
-pub fn apply_operation(image: &DynamicImage, op: Operation, state: &AppState) -> DynamicImage {
-    // we dont want a stack overflow do we
-    if state.last_operation == Operation::Save && state.selected_operation == Operation::Save {
-        panic!("uh oh")
+// Salva o resultado ja processado, devolvendo a mensagem de sucesso ou erro para `status`
+pub fn save_result(data: &AppState, path: &Path, image: &DynamicImage) -> String {
+    let options = SaveOptions {
+        format: data.save_format,
+        quality: data.jpeg_quality.round() as u8,
+        recipe: if data.embed_recipe {
+            Some(current_recipe(data))
+        } else {
+            None
+        },
+    };
+
+    match save_image(image, path, &options) {
+        Ok(path) => format!("Salvo em {}", path.display()),
+        Err(err) => err.to_string(),
     }
+}
+
+pub fn apply_operation(image: &DynamicImage, op: &Operation, state: &AppState) -> DynamicImage {
     match op {
         Operation::FlipH => image.flip_h(),
         Operation::FlipV => image.flip_v(),
-        Operation::Save => apply_operation(image, state.last_operation, state),
         Operation::Grayscale => image.to_grayscale_rgb(),
         Operation::Quantize => image.quantize_grayscale(state.param1 as u8),
         Operation::None => image.clone(),
-        Operation::AdjustBrightness => image.adjust_brightness(state.param1 as u8),
-        Operation::AdjustContrast => image.adjust_contrast_2(state.param1 as u8),
+        Operation::AdjustBrightness => {
+            image.adjust_brightness(state.param1.round().clamp(-255.0, 255.0) as i16)
+        }
+        Operation::AdjustContrast => image.adjust_contrast_2(state.param1 as f32),
+        Operation::Gamma => image.gamma_correction(state.param1 as f32),
+        Operation::ToneCurve(curve) => image.tone_curve(curve),
         Operation::Negative => image.negative(),
-        Operation::ZoomOut => image.zoom_out(state.param2 as u8, state.param3 as u8),
-        Operation::ZoomIn => image.zoom_in(),
-        Operation::Convolution(kernel) => image.convolution(kernel),
+        Operation::ZoomOut => match zoom_factors(state) {
+            Some((scale_w, scale_h)) => {
+                let (w, h) = image.get_dimensions();
+                let new_w = (w as f64 / scale_w).round() as u32;
+                let new_h = (h as f64 / scale_h).round() as u32;
+
+                ImageExt2::resize(image, new_w, new_h, state.resize_filter)
+            }
+            None => image.clone(),
+        },
+        Operation::ZoomIn => match zoom_factors(state) {
+            Some((scale_w, scale_h)) => {
+                let (w, h) = image.get_dimensions();
+                let new_w = (w as f64 * scale_w).round() as u32;
+                let new_h = (h as f64 * scale_h).round() as u32;
+
+                ImageExt2::resize(image, new_w, new_h, state.resize_filter)
+            }
+            None => image.clone(),
+        },
+        Operation::Resize => {
+            let (new_w, new_h) = resize_dimensions(
+                image.get_dimensions(),
+                state.resize_width,
+                state.resize_height,
+            );
+
+            ImageExt2::resize(image, new_w, new_h, state.resize_filter)
+        }
+        Operation::Convolution(kernel, normalization) => image.convolution(
+            kernel,
+            state.border_mode,
+            state.convolution_color,
+            *normalization,
+        ),
+        Operation::Equalize(mode) => image.histogram_equalize(*mode),
+        // sem alvo, ou com um alvo que nao abre, a imagem fica inalterada
+        Operation::HistogramMatch => match state.target_image.as_ref().map(image::open) {
+            Some(Ok(target)) => image.histogram_match(&target),
+            // o alvo e conferido ao ser escolhido, em `select_target` e no --target da CLI
+            _ => image.clone(),
+        },
+        Operation::RotateCw => image.rotate_90_cw(),
+        Operation::RotateCcw => image.rotate_90_ccw(),
+        Operation::Rotate(interpolation) => {
+            let canvas = if state.expand_canvas {
+                RotateCanvas::Expand
+            } else {
+                RotateCanvas::Crop
+            };
+
+            image.rotate(state.angle as f32, *interpolation, canvas)
+        }
+        Operation::Canny => image.canny(
+            state.param1 as f32,
+            state.param2 as f32,
+            state.param3 as f32,
+        ),
+        Operation::Gradient(output) => {
+            image.gradient(state.gradient_operator, *output, state.param1 as f32)
+        }
+        Operation::Pipeline(pipeline) => pipeline.apply(image, state),
     }
 }
 
@@ -483,8 +1217,15 @@ pub fn apply_operation(image: &DynamicImage, op: Operation, state: &AppState) ->
 mod tests {
     use std::{error::Error, path::Path};
 
-    use crate::imageops::ImageExt;
-    use image::{DynamicImage, GenericImage, GenericImageView};
+    use crate::imageops::Operation;
+    use crate::imageops::{ImageExt, Interpolation, RotateCanvas};
+    use crate::precision::convert_to;
+    use crate::{
+        apply_operation, only_text_fields_changed, open_path, resize_dimensions, select_operation,
+        select_target, AppState,
+    };
+    use image::{ColorType, DynamicImage, GenericImage, GenericImageView, ImageBuffer};
+    use proptest::{collection::vec, prelude::*};
     #[test]
     fn read_as_bytes() -> Result<(), Box<dyn Error>> {
         let image_folder_path = Path::new(&std::env::current_dir().unwrap())
@@ -516,4 +1257,204 @@ mod tests {
         assert_eq!(flip_v_fast, flip_v_naive);
         Ok(())
     }
+
+    const COLORS: [ColorType; 10] = [
+        ColorType::L8,
+        ColorType::La8,
+        ColorType::Rgb8,
+        ColorType::Rgba8,
+        ColorType::L16,
+        ColorType::La16,
+        ColorType::Rgb16,
+        ColorType::Rgba16,
+        ColorType::Rgb32F,
+        ColorType::Rgba32F,
+    ];
+
+    // Imagem de qualquer tipo de pixel e tamanho, incluindo dimensoes impares e vazias
+    fn any_image() -> impl Strategy<Value = DynamicImage> {
+        (0u32..12, 0u32..12, 0..COLORS.len()).prop_flat_map(|(w, h, color)| {
+            vec(any::<u16>(), (w * h * 4) as usize).prop_map(move |raw| {
+                let image = DynamicImage::ImageRgba16(ImageBuffer::from_raw(w, h, raw).unwrap());
+                convert_to(&image, COLORS[color])
+            })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn flip_eq_image_flip(img in any_image()) {
+            prop_assert_eq!(img.flip_v(), img.flipv());
+            prop_assert_eq!(img.flip_h(), img.fliph());
+            prop_assert_eq!(img.flip_v().flip_v(), img.clone());
+            prop_assert_eq!(img.flip_h().flip_h(), img);
+        }
+    }
+
+    #[test]
+    fn text_fields_keep_ui() {
+        let state = AppState::new();
+
+        // digitar numa caixa de texto nao reconstroi a interface
+        let mut typed = state.clone();
+        typed.kernel_editor.preset_name = "meu kernel".to_owned();
+        std::sync::Arc::make_mut(&mut typed.kernel_editor.weights)[0] = 0.5;
+        typed.recipe_path = "outra.json".to_owned();
+        typed.path_input = "/tmp/imagem.png".to_owned();
+        typed.resize_width = 640;
+        assert!(only_text_fields_changed(&state, &typed));
+
+        let mut resized = state.clone();
+        resized.kernel_editor.resize(5);
+        assert!(!only_text_fields_changed(&state, &resized));
+
+        let mut selected = state.clone();
+        selected.selected_operation = Operation::Negative;
+        assert!(!only_text_fields_changed(&state, &selected));
+    }
+
+    #[test]
+    fn neutral_param1_on_select() {
+        let mut state = AppState::new();
+
+        select_operation(&mut state, Operation::AdjustContrast);
+        assert_eq!(state.param1, 1.0);
+        select_operation(&mut state, Operation::AdjustBrightness);
+        assert_eq!(state.param1, 0.0);
+        select_operation(&mut state, Operation::Gamma);
+        assert_eq!(state.param1, 1.0);
+        select_operation(&mut state, Operation::AdjustBrightness);
+
+        // escolher de novo a mesma operacao mantem o valor ajustado
+        state.param1 = 40.0;
+        select_operation(&mut state, Operation::AdjustBrightness);
+        assert_eq!(state.param1, 40.0);
+
+        select_operation(&mut state, Operation::Quantize);
+        assert_eq!(state.param1, 40.0);
+
+        // o param2 do Canny vai ate 255 e nao pode chegar ao zoom como fator
+        select_operation(&mut state, Operation::Canny);
+        state.param2 = 0.0;
+        state.param3 = 200.0;
+        select_operation(&mut state, Operation::ZoomOut);
+        assert_eq!((state.param2, state.param3), (1.0, 5.0));
+
+        let image = image::DynamicImage::new_rgb8(8, 8);
+        state.param2 = 0.0;
+        assert_eq!(apply_operation(&image, &Operation::ZoomOut, &state), image);
+        state.param2 = f64::NAN;
+        assert_eq!(apply_operation(&image, &Operation::ZoomIn, &state), image);
+    }
+
+    #[test]
+    fn resize_to_exact_dimensions() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(image_folder_path.join("Gramado_22k.jpg"))?;
+        let (w, h) = img.dimensions();
+
+        let mut state = AppState::new();
+        state.resize_width = 301;
+        state.resize_height = 127;
+        let resized = apply_operation(&img, &Operation::Resize, &state);
+        assert_eq!(resized.dimensions(), (301, 127));
+
+        // um lado em 0 segue a proporcao da imagem
+        assert_eq!(resize_dimensions((w, h), 0, 0), (w, h));
+        assert_eq!(resize_dimensions((200, 100), 50, 0), (50, 25));
+        assert_eq!(resize_dimensions((200, 100), 0, 300), (600, 300));
+        assert_eq!(resize_dimensions((200, 1), 10, 0), (10, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_90_eq() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        assert_eq!(img.rotate_90_cw(), img.rotate90());
+        assert_eq!(img.rotate_90_ccw(), img.rotate270());
+        assert_eq!(img.rotate_90_cw().rotate_90_ccw(), img);
+
+        // rotacoes multiplas de 90 graus com vizinho mais proximo sao exatas
+        for (angle, expected) in [(90.0, img.rotate270()), (-90.0, img.rotate90())].iter() {
+            let rotated = img.rotate(*angle, Interpolation::Nearest, RotateCanvas::Expand);
+            assert_eq!(&rotated, expected);
+        }
+
+        // as rotacoes mantem o tipo da imagem, sem perder os 16 bits
+        let deep = DynamicImage::ImageLuma16(ImageBuffer::from_fn(64, 32, |x, y| {
+            image::Luma([(x * 1000 + y) as u16])
+        }));
+        for image in [&img, &deep].iter() {
+            for rotated in [
+                image.rotate_90_cw(),
+                image.rotate_90_ccw(),
+                image.rotate(30.0, Interpolation::Bicubic, RotateCanvas::Expand),
+            ]
+            .iter()
+            {
+                assert_eq!(rotated.color(), image.color());
+            }
+        }
+        assert_eq!(
+            deep.rotate(90.0, Interpolation::Nearest, RotateCanvas::Expand),
+            deep.rotate270()
+        );
+
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+        ]
+        .iter()
+        {
+            let rotated = img.rotate(0.0, *interpolation, RotateCanvas::Crop);
+            assert_eq!(rotated, img);
+            let rotated = deep.rotate(0.0, *interpolation, RotateCanvas::Crop);
+            assert_eq!(rotated, deep);
+
+            let rotated = img.rotate(30.0, *interpolation, RotateCanvas::Crop);
+            assert_eq!(rotated.dimensions(), img.dimensions());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_path_file_or_folder() {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let mut state = AppState::new();
+
+        open_path(&mut state, &image_folder_path.join("Gramado_22k.jpg"));
+        assert!(state
+            .selected_image
+            .as_ref()
+            .unwrap()
+            .ends_with("Gramado_22k.jpg"));
+
+        open_path(&mut state, &image_folder_path);
+        assert_eq!(Path::new(&state.image_folder), image_folder_path);
+
+        let image_folder = state.image_folder.clone();
+        open_path(&mut state, &image_folder_path.join("nao-existe.png"));
+        assert_eq!(state.image_folder, image_folder);
+        assert!(state.status.starts_with("Caminho nao encontrado"));
+
+        let target = image_folder_path.join("Gramado_22k.jpg");
+        select_target(&mut state, target.to_str().unwrap());
+        assert_eq!(state.target_image.as_deref(), target.to_str());
+
+        select_target(&mut state, "nao-existe.png");
+        assert_eq!(state.target_image.as_deref(), target.to_str());
+        assert!(state.status.starts_with("Erro ao abrir o alvo"));
+    }
 }
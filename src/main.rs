@@ -1,26 +1,41 @@
 use druid::widget::prelude::*;
 use druid::{AppLauncher, WindowDesc};
-use fpi::{imageops::Operation, AppState, UiBuilder};
+use fpi::{default_image_folder, imageops::Operation, AppState, Delegate, UiBuilder};
 
 fn make_ui() -> impl Widget<AppState> {
     UiBuilder::new()
 }
 
 pub fn main() {
-    let main_window = WindowDesc::new(|| make_ui())
+    // com argumentos roda sem interface grafica
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(err) = fpi::cli::run(&args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let main_window = WindowDesc::new(make_ui)
         .window_size((1280., 960.))
         .title("Fpi - 2020 - Matheus Leite Cruz");
 
+    // abre com a imagem de exemplo e as demais opcoes padrao do AppState
+    let default_image = default_image_folder().join("Gramado_22k.jpg");
     let state = AppState {
-        selected_image: "Gramado_22k.jpg".to_owned().into(),
+        selected_image: Some(default_image)
+            .filter(|path| path.is_file())
+            .map(|path| path.to_string_lossy().into_owned()),
         selected_operation: Operation::None,
-        param1: 64.0,
+        last_operation: Operation::None,
         param2: 2.0,
         param3: 2.0,
-        last_operation: Operation::None,
+        ..AppState::new()
     };
 
     AppLauncher::with_window(main_window)
+        .delegate(Delegate)
         .launch(state)
         .expect("Failed to launch application");
 }
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use druid::{
+    widget::{Button, Checkbox, Flex, Label, TextBox},
+    Data, Lens, Widget, WidgetExt,
+};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{history::Step, imageops::Operation, recipe::Recipe, select_operation, AppState};
+
+#[derive(Debug, Data, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineStep {
+    #[serde(flatten)]
+    pub step: Step,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+// Sequencia de operacoes, cada uma com seus parametros, aplicadas em ordem
+#[derive(Debug, Data, Clone, Default, PartialEq, Lens, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub steps: Arc<Vec<PipelineStep>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, step: Step) {
+        Arc::make_mut(&mut self.steps).push(PipelineStep {
+            step,
+            enabled: true,
+        });
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.steps.len() {
+            Arc::make_mut(&mut self.steps).remove(idx);
+        }
+    }
+
+    pub fn move_up(&mut self, idx: usize) {
+        if idx > 0 && idx < self.steps.len() {
+            Arc::make_mut(&mut self.steps).swap(idx - 1, idx);
+        }
+    }
+
+    pub fn move_down(&mut self, idx: usize) {
+        if idx + 1 < self.steps.len() {
+            Arc::make_mut(&mut self.steps).swap(idx, idx + 1);
+        }
+    }
+
+    pub fn toggle(&mut self, idx: usize) {
+        if let Some(step) = Arc::make_mut(&mut self.steps).get_mut(idx) {
+            step.enabled = !step.enabled;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.steps = Arc::new(vec![]);
+    }
+
+    // Aplica os passos habilitados, cada um sobre o resultado do anterior
+    pub fn apply(&self, image: &DynamicImage, state: &AppState) -> DynamicImage {
+        self.apply_until(image, state, &|| false).unwrap()
+    }
+
+    // Como `apply`, mas desiste antes de cada passo se `cancelled` devolver true
+    pub fn apply_until(
+        &self,
+        image: &DynamicImage,
+        state: &AppState,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Option<DynamicImage> {
+        let mut image = image.clone();
+        for pipeline_step in self.steps.iter().filter(|step| step.enabled) {
+            if cancelled() {
+                return None;
+            }
+            image = pipeline_step.step.apply(&image, state);
+        }
+
+        Some(image)
+    }
+}
+
+pub fn build_pipeline_editor(state: &AppState) -> impl Widget<AppState> {
+    let mut col = Flex::column().with_child(Label::new("Pipeline:"));
+
+    for (idx, pipeline_step) in state.pipeline.steps.iter().enumerate() {
+        let label = format!("{}. {:?}", idx + 1, pipeline_step.step.operation);
+
+        let row = Flex::row()
+            .with_child(Checkbox::new("").lens(druid::lens::Map::new(
+                move |data: &AppState| {
+                    data.pipeline
+                        .steps
+                        .get(idx)
+                        .map(|pipeline_step| pipeline_step.enabled)
+                        .unwrap_or(false)
+                },
+                move |data: &mut AppState, enabled: bool| {
+                    if let Some(pipeline_step) =
+                        Arc::make_mut(&mut data.pipeline.steps).get_mut(idx)
+                    {
+                        pipeline_step.enabled = enabled;
+                    }
+                },
+            )))
+            .with_flex_child(Label::new(label), 1.0)
+            .with_child(
+                Button::new("^")
+                    .on_click(move |_ctx, data: &mut AppState, _env| data.pipeline.move_up(idx)),
+            )
+            .with_child(
+                Button::new("v")
+                    .on_click(move |_ctx, data: &mut AppState, _env| data.pipeline.move_down(idx)),
+            )
+            .with_child(
+                Button::new("x")
+                    .on_click(move |_ctx, data: &mut AppState, _env| data.pipeline.remove(idx)),
+            );
+
+        col.add_child(row);
+    }
+
+    let action_row = Flex::row()
+        .with_child(
+            Button::new("Adicionar").on_click(|_ctx, data: &mut AppState, _env| {
+                let op = data.selected_operation.clone();
+                if op != Operation::None {
+                    let step = Step::from_state(op, data);
+                    data.pipeline.push(step);
+                }
+            }),
+        )
+        .with_child(
+            Button::new("Executar").on_click(|_ctx, data: &mut AppState, _env| {
+                let op = Operation::Pipeline(data.pipeline.clone());
+                select_operation(data, op);
+            }),
+        )
+        .with_child(
+            Button::new("Limpar").on_click(|_ctx, data: &mut AppState, _env| data.pipeline.clear()),
+        );
+    col.add_child(action_row);
+
+    let recipe_row =
+        Flex::row()
+            .with_child(
+                TextBox::new()
+                    .with_placeholder("receita.json")
+                    .lens(AppState::recipe_path)
+                    .fix_width(150.0),
+            )
+            .with_child(Button::new("Salvar Receita").on_click(
+                |_ctx, data: &mut AppState, _env| {
+                    data.status = match Recipe::new(data.pipeline.clone()).save(&data.recipe_path) {
+                        Ok(()) => format!("Receita salva em {}", data.recipe_path),
+                        Err(err) => err.to_string(),
+                    };
+                },
+            ))
+            .with_child(Button::new("Carregar Receita").on_click(
+                |_ctx, data: &mut AppState, _env| match Recipe::load(&data.recipe_path) {
+                    Ok(recipe) => {
+                        data.status = format!("Receita carregada de {}", data.recipe_path);
+                        data.pipeline = recipe.pipeline;
+                        let op = Operation::Pipeline(data.pipeline.clone());
+                        select_operation(data, op);
+                    }
+                    Err(err) => data.status = err.to_string(),
+                },
+            ));
+    col.add_child(recipe_row);
+
+    col
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, path::Path};
+
+    use crate::history::Step;
+    use crate::imageops::{ImageExt, Operation};
+    use crate::imageops2::{ImageExt2, Kernel, Normalization, GAUSSIANO, LAPLACIANO};
+    use crate::pipeline::Pipeline;
+    use crate::AppState;
+
+    #[test]
+    fn test_pipeline() -> Result<(), Box<dyn Error>> {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+
+        let mut state = AppState::new();
+        let gaussian = Operation::Convolution(GAUSSIANO.into(), Normalization::Clamp);
+        let laplacian = Operation::Convolution(LAPLACIANO.into(), Normalization::Clamp);
+
+        let mut pipeline = Pipeline::new();
+        pipeline.push(Step::from_state(Operation::Grayscale, &state));
+        pipeline.push(Step::from_state(gaussian.clone(), &state));
+        pipeline.push(Step::from_state(laplacian.clone(), &state));
+        state.param1 = 4.0;
+        pipeline.push(Step::from_state(Operation::Quantize, &state));
+
+        let (border, color) = (state.border_mode, state.convolution_color);
+        let convolve = |img: &image::DynamicImage, kernel: [f32; 9]| {
+            img.convolution(&Kernel::from(kernel), border, color, Normalization::Clamp)
+        };
+
+        state.param1 = 128.0;
+        let expected = convolve(&convolve(&img.to_grayscale_rgb(), GAUSSIANO), LAPLACIANO)
+            .quantize_grayscale(4);
+        assert_eq!(pipeline.apply(&img, &state), expected);
+
+        // passos desabilitados sao ignorados
+        pipeline.toggle(3);
+        let expected = convolve(&convolve(&img.to_grayscale_rgb(), GAUSSIANO), LAPLACIANO);
+        assert_eq!(pipeline.apply(&img, &state), expected);
+        pipeline.toggle(3);
+
+        // reordenar: [cinza, gauss, laplace, quant] -> [gauss, cinza, quant, laplace]
+        pipeline.move_up(3);
+        pipeline.move_down(0);
+        let operations: Vec<Operation> = pipeline
+            .steps
+            .iter()
+            .map(|pipeline_step| pipeline_step.step.operation.clone())
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                gaussian,
+                Operation::Grayscale,
+                Operation::Quantize,
+                laplacian
+            ]
+        );
+
+        pipeline.remove(0);
+        assert_eq!(pipeline.steps.len(), 3);
+        pipeline.clear();
+        assert_eq!(pipeline.apply(&img, &state), img);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use image::{ColorType, DynamicImage, GenericImageView, Rgba32FImage};
+
+// Tons distintos usados nos histogramas de imagens com mais de 8 bits
+pub const HIGH_PRECISION_BINS: usize = 65536;
+
+// Imagens com 16 bits inteiros ou ponto flutuante por canal
+pub fn is_high_precision(image: &DynamicImage) -> bool {
+    !matches!(
+        image.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    )
+}
+
+pub fn is_float(image: &DynamicImage) -> bool {
+    matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+// Converte para o tipo de pixel pedido; inteiros sao limitados a faixa do tipo
+pub fn convert_to(image: &DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+        _ => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+    }
+}
+
+// Mesmo layout de canais com 8 bits, para operacoes que so trabalham com u8
+pub fn to_8bit(image: &DynamicImage) -> DynamicImage {
+    let color = match image.color() {
+        ColorType::L8 | ColorType::L16 => ColorType::L8,
+        ColorType::La8 | ColorType::La16 => ColorType::La8,
+        ColorType::Rgb8 | ColorType::Rgb16 | ColorType::Rgb32F => ColorType::Rgb8,
+        _ => ColorType::Rgba8,
+    };
+
+    convert_to(image, color)
+}
+
+// Tipo de cinza com a mesma precisao e alfa da imagem. Nao existe cinza em ponto
+// flutuante, entao imagens HDR ficam em RGB com os tres canais iguais
+pub fn grayscale_color(image: &DynamicImage) -> ColorType {
+    match (is_float(image), image.color().has_alpha()) {
+        (true, true) => ColorType::Rgba32F,
+        (true, false) => ColorType::Rgb32F,
+        (false, true) => ColorType::La16,
+        (false, false) => ColorType::L16,
+    }
+}
+
+// Pixels em RGBA de ponto flutuante, com 1.0 como valor maximo do tipo original
+pub fn float_pixels(image: &DynamicImage) -> Vec<[f32; 4]> {
+    image.to_rgba32f().pixels().map(|pixel| pixel.0).collect()
+}
+
+pub fn from_float_pixels(
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+    color: ColorType,
+) -> DynamicImage {
+    let raw = pixels.into_iter().flatten().collect();
+    let image = DynamicImage::ImageRgba32F(Rgba32FImage::from_raw(width, height, raw).unwrap());
+
+    convert_to(&image, color)
+}
+
+// Aplica `f` as cores de cada pixel, mantendo o alfa e o tipo da imagem
+pub fn map_color(image: &DynamicImage, f: impl Fn(f32) -> f32) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let mut pixels = float_pixels(image);
+
+    for pixel in pixels.iter_mut() {
+        for value in pixel.iter_mut().take(3) {
+            *value = f(*value);
+        }
+    }
+
+    from_float_pixels(width, height, pixels, image.color())
+}
+
+pub fn float_luma(pixel: &[f32; 4]) -> f32 {
+    0.299 * pixel[0] + 0.587 * pixel[1] + 0.114 * pixel[2]
+}
+
+pub fn bin(value: f32, bins: usize) -> usize {
+    (value.clamp(0.0, 1.0) * (bins - 1) as f32).round() as usize
+}
+
+// Histograma de valores em 0..1; valores HDR acima de 1 vao para a ultima coluna
+pub fn unit_histogram(values: impl Iterator<Item = f32>, bins: usize) -> Vec<u32> {
+    let mut histogram = vec![0; bins];
+    for value in values {
+        histogram[bin(value, bins)] += 1;
+    }
+
+    histogram
+}
+
+fn cumulative(histogram: &[u32]) -> Vec<u32> {
+    histogram
+        .iter()
+        .scan(0, |acc, count| {
+            *acc += count;
+            Some(*acc)
+        })
+        .collect()
+}
+
+// Tabela de equalizacao com o novo valor, em 0..1, de cada coluna
+pub fn equalization_lut(histogram: &[u32]) -> Vec<f32> {
+    let cumulative = cumulative(histogram);
+    let total = (*cumulative.last().unwrap_or(&0)).max(1) as f32;
+
+    cumulative
+        .iter()
+        .map(|count| *count as f32 / total)
+        .collect()
+}
+
+// Tabela que leva cada coluna da origem a coluna do alvo com a frequencia acumulada
+// mais proxima, preferindo a primeira em caso de empate. Como as frequencias acumuladas
+// sao crescentes, a busca e binaria
+pub fn match_lut(source: &[u32], target: &[u32]) -> Vec<f32> {
+    let source = cumulative(source);
+    let target = cumulative(target);
+    let source_total = (*source.last().unwrap_or(&0)).max(1) as f64;
+    let target_total = (*target.last().unwrap_or(&0)).max(1) as f64;
+    let target: Vec<f64> = target.iter().map(|c| *c as f64 / target_total).collect();
+    let last = target.len() - 1;
+
+    source
+        .iter()
+        .map(|count| {
+            let freq = *count as f64 / source_total;
+
+            // primeira coluna com frequencia >= freq e o inicio do patamar anterior a ela
+            let above = target.partition_point(|t| *t < freq).min(last);
+            let best = if above == 0 {
+                0
+            } else {
+                let below = target[above - 1];
+                if target[above] - freq < freq - below {
+                    above
+                } else {
+                    target.partition_point(|t| *t < below)
+                }
+            };
+
+            best as f32 / last as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GenericImageView, ImageBuffer, Luma, Rgb};
+
+    use crate::imageops::{ImageExt, ToDruidImage};
+    use crate::imageops2::{
+        BorderMode, ConvolutionColor, EqualizeMode, ImageExt2, Kernel, Normalization, ResizeFilter,
+    };
+    use crate::precision::{float_pixels, is_high_precision};
+
+    fn distinct(image: &DynamicImage) -> usize {
+        let mut values: Vec<u16> = image.to_luma16().into_raw();
+        values.sort_unstable();
+        values.dedup();
+        values.len()
+    }
+
+    #[test]
+    fn test_high_precision() {
+        // rampa de 16 bits com 4096 tons, que se perderiam numa conversao para 8 bits
+        let ramp = DynamicImage::ImageLuma16(ImageBuffer::from_fn(64, 64, |x, y| {
+            Luma([((y * 64 + x) * 16) as u16])
+        }));
+        assert!(is_high_precision(&ramp));
+        assert_eq!(distinct(&ramp), 4096);
+
+        let negative = ramp.negative();
+        assert_eq!(negative.color(), ramp.color());
+        assert_eq!(negative.negative(), ramp);
+        assert_eq!(ramp.adjust_contrast_2(1.0), ramp);
+        assert_eq!(ramp.adjust_brightness(0), ramp);
+        assert!(distinct(&ramp.adjust_brightness(10)) > 256);
+
+        let rgb = DynamicImage::ImageRgb16(ramp.to_rgb16());
+        let identity = Kernel::new(3, 3, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        for color in [
+            ConvolutionColor::Grayscale,
+            ConvolutionColor::PerChannel,
+            ConvolutionColor::Luminance,
+        ]
+        .iter()
+        {
+            let convolved =
+                rgb.convolution(&identity, BorderMode::Clamp, *color, Normalization::Clamp);
+            assert_eq!(convolved.color(), rgb.color());
+            assert_eq!(distinct(&convolved), 4096);
+        }
+
+        assert_eq!(ramp.to_grayscale(), ramp);
+        assert_eq!(ramp.histogram_match(&rgb), ramp);
+        assert_eq!(rgb.histogram_match(&rgb), rgb);
+
+        let equalized = ramp.histogram_equalize(EqualizeMode::Grayscale);
+        assert_eq!(equalized.color(), ramp.color());
+        assert!(distinct(&equalized) > 256);
+        for mode in [EqualizeMode::PerChannel, EqualizeMode::Luminance].iter() {
+            assert_eq!(rgb.histogram_equalize(*mode).color(), rgb.color());
+        }
+
+        let (w, h) = ramp.dimensions();
+        assert_eq!(ImageExt2::resize(&ramp, w, h, ResizeFilter::Bilinear), ramp);
+
+        // o histograma exibido continua com 256 colunas somando todos os pixels
+        let total: u32 = ramp.grayscale_histogram().iter().sum();
+        assert_eq!(total, w * h);
+    }
+
+    #[test]
+    fn test_float_hdr() {
+        // valores acima de 1.0 sao mantidos pelas operacoes e so limitados na exibicao
+        let hdr = DynamicImage::ImageRgb32F(ImageBuffer::from_fn(8, 8, |x, _| {
+            Rgb([x as f32 * 0.5, 0.25, 2.0])
+        }));
+
+        let brighter = hdr.adjust_contrast_2(2.0);
+        assert_eq!(brighter.color(), hdr.color());
+        assert_eq!(float_pixels(&brighter)[7], [7.0, 0.5, 4.0, 1.0]);
+
+        let blurred = hdr.convolution(
+            &Kernel::box_blur(3),
+            BorderMode::Clamp,
+            ConvolutionColor::PerChannel,
+            Normalization::Clamp,
+        );
+        assert!((float_pixels(&blurred)[9][2] - 2.0).abs() < 1e-4);
+
+        assert_eq!(hdr.to_grayscale().color(), hdr.color());
+        assert_eq!(hdr.to_rgb8().get_pixel(7, 0).0, [255, 64, 255]);
+        hdr.to_druid_image();
+    }
+}
@@ -0,0 +1,192 @@
+use std::{fmt, fs, io, path::Path};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{export::read_embedded_recipe, pipeline::Pipeline, AppState};
+
+// Versao atual do formato. Ao mudar o formato, incremente e adicione a migracao em `migrate`
+pub const RECIPE_VERSION: u32 = 1;
+
+// Pipeline salvo em disco como JSON, junto com a versao do formato
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+    pub version: u32,
+    pub pipeline: Pipeline,
+}
+
+#[derive(Debug)]
+pub enum RecipeError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipeError::Io(err) => write!(f, "erro ao acessar a receita: {}", err),
+            RecipeError::Json(err) => write!(f, "receita invalida: {}", err),
+            RecipeError::UnsupportedVersion(version) => write!(
+                f,
+                "receita na versao {}, mas a maior versao suportada e {}",
+                version, RECIPE_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecipeError {}
+
+impl From<io::Error> for RecipeError {
+    fn from(err: io::Error) -> Self {
+        RecipeError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for RecipeError {
+    fn from(err: serde_json::Error) -> Self {
+        RecipeError::Json(err)
+    }
+}
+
+// Atualiza o JSON de versoes anteriores para a versao atual.
+// Receitas sem o campo `version` sao tratadas como versao 1
+fn migrate(mut value: Value) -> Result<Value, RecipeError> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+
+    if version > RECIPE_VERSION {
+        return Err(RecipeError::UnsupportedVersion(version));
+    }
+
+    value["version"] = Value::from(RECIPE_VERSION);
+    Ok(value)
+}
+
+impl Recipe {
+    pub fn new(pipeline: Pipeline) -> Self {
+        Self {
+            version: RECIPE_VERSION,
+            pipeline,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, RecipeError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, RecipeError> {
+        let value = migrate(serde_json::from_str(json)?)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RecipeError> {
+        Ok(fs::write(path, self.to_json()?)?)
+    }
+
+    // Aceita um .json ou um PNG salvo com a receita embutida
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecipeError> {
+        let bytes = fs::read(path)?;
+        if let Some(json) = read_embedded_recipe(&bytes) {
+            return Self::from_json(&json);
+        }
+
+        let json = String::from_utf8(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Self::from_json(&json)
+    }
+
+    // Aplica a receita sem interface grafica, partindo dos parametros padrao
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        self.pipeline.apply(image, &AppState::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, path::Path};
+
+    use crate::history::Step;
+    use crate::imageops::Operation;
+    use crate::imageops2::{Kernel, Normalization};
+    use crate::pipeline::Pipeline;
+    use crate::recipe::{Recipe, RecipeError, RECIPE_VERSION};
+    use crate::AppState;
+
+    #[test]
+    fn test_recipe_roundtrip() -> Result<(), Box<dyn Error>> {
+        let mut state = AppState::new();
+        let mut pipeline = Pipeline::new();
+        pipeline.push(Step::from_state(Operation::Grayscale, &state));
+        pipeline.push(Step::from_state(
+            Operation::Convolution(Kernel::gaussian(5, 1.0), Normalization::Clamp),
+            &state,
+        ));
+        state.param1 = 8.0;
+        pipeline.push(Step::from_state(Operation::Quantize, &state));
+        pipeline.toggle(1);
+
+        let recipe = Recipe::new(pipeline);
+        let json = recipe.to_json()?;
+        assert!(json.contains("\"version\": 1"));
+        assert!(json.contains("\"Quantize\""));
+        assert_eq!(Recipe::from_json(&json)?, recipe);
+
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let img = image::open(dbg!(image_folder_path.join("Gramado_22k.jpg")))?;
+        assert_eq!(
+            Recipe::from_json(&json)?.apply(&img),
+            recipe.pipeline.apply(&img, &AppState::new())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipe_versions() -> Result<(), Box<dyn Error>> {
+        // receitas sem versao e com parametros faltando usam os valores padrao
+        let old = r#"{"pipeline": {"steps": [{"operation": "Negative"}, {"operation": "Quantize", "param1": 4.0, "enabled": false}]}}"#;
+        let recipe = Recipe::from_json(old)?;
+        assert_eq!(recipe.version, RECIPE_VERSION);
+        assert_eq!(recipe.pipeline.steps.len(), 2);
+        assert!(recipe.pipeline.steps[0].enabled);
+        assert_eq!(recipe.pipeline.steps[1].step.param1, 4.0);
+        assert_eq!(recipe.pipeline.steps[0].step.param1, AppState::new().param1);
+
+        let future = r#"{"version": 99, "pipeline": {"steps": []}}"#;
+        assert!(matches!(
+            Recipe::from_json(future),
+            Err(RecipeError::UnsupportedVersion(99))
+        ));
+
+        assert!(matches!(Recipe::from_json("{"), Err(RecipeError::Json(_))));
+
+        // kernels invalidos sao recusados ao carregar, antes de chegar na convolucao
+        let convolution = |kernel: &str| {
+            format!(
+                r#"{{"pipeline": {{"steps": [{{"operation": {{"Convolution": [{}, "Clamp"]}}}}]}}}}"#,
+                kernel
+            )
+        };
+        for kernel in [
+            r#"{"Dense": {"width": 0, "height": 3, "weights": []}}"#,
+            r#"{"Dense": {"width": 2, "height": 3, "weights": [0, 0, 0, 0, 0, 0]}}"#,
+            r#"{"Dense": {"width": 3, "height": 3, "weights": [1]}}"#,
+            r#"{"Separable": {"horizontal": [], "vertical": [1]}}"#,
+        ]
+        .iter()
+        {
+            match Recipe::from_json(&convolution(kernel)) {
+                Err(RecipeError::Json(err)) => assert!(err.to_string().contains("kernel")),
+                other => panic!("kernel invalido aceito: {:?}", other),
+            }
+        }
+        let valid = r#"{"Dense": {"width": 3, "height": 1, "weights": [1, 2, 1]}}"#;
+        assert!(Recipe::from_json(&convolution(valid)).is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use druid::Selector;
+
+use crate::{
+    batch::panic_message,
+    cache::{ImageCache, RenderedImages},
+    AppState,
+};
+
+// Enviado pela thread de processamento quando um resultado fica pronto
+pub const RENDER_DONE: Selector<RenderOutput> = Selector::new("fpi.render-done");
+
+struct RenderJob {
+    generation: u64,
+    state: AppState,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOutput {
+    pub generation: u64,
+    pub images: Option<Result<RenderedImages, String>>,
+}
+
+// Processa as imagens numa thread separada para nao travar a janela.
+// Cada pedido recebe uma geracao; pedidos que ficaram na fila atras de um mais novo sao
+// descartados sem processar, o processamento de um pedido ultrapassado e interrompido
+// entre os passos do historico e do pipeline, e seus resultados nao sao entregues.
+// Um panic durante o processamento e entregue como erro, e a thread continua atendendo
+pub struct Worker {
+    sender: Sender<RenderJob>,
+    latest: Arc<AtomicU64>,
+}
+
+impl Worker {
+    pub fn spawn(on_done: impl Fn(RenderOutput) + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel::<RenderJob>();
+        let latest = Arc::new(AtomicU64::new(0));
+        let current = latest.clone();
+
+        // a thread termina quando o Worker e descartado e o canal fecha
+        thread::spawn(move || {
+            let mut cache = ImageCache::new();
+
+            while let Ok(mut job) = receiver.recv() {
+                while let Ok(newer) = receiver.try_recv() {
+                    job = newer;
+                }
+
+                let stale = || job.generation != current.load(Ordering::SeqCst);
+                if stale() {
+                    continue;
+                }
+
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    cache.update_until(&job.state, &stale)
+                }));
+                let images = match result {
+                    Ok(Ok(images)) => images,
+                    Ok(Err(_cancelled)) => continue,
+                    Err(payload) => {
+                        // o cache pode ter ficado pela metade, entao recomeca vazio
+                        cache = ImageCache::new();
+                        Some(Err(format!(
+                            "Falha ao processar a imagem: {}",
+                            panic_message(payload.as_ref())
+                        )))
+                    }
+                };
+
+                if !stale() {
+                    on_done(RenderOutput {
+                        generation: job.generation,
+                        images,
+                    });
+                }
+            }
+        });
+
+        Self { sender, latest }
+    }
+
+    // Pede o processamento do estado e devolve a geracao do pedido
+    pub fn submit(&self, state: &AppState) -> u64 {
+        let generation = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let _ = self.sender.send(RenderJob {
+            generation,
+            state: state.clone(),
+        });
+
+        generation
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.latest.load(Ordering::SeqCst) == generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::{mpsc, Arc},
+        time::Duration,
+    };
+
+    use crate::imageops::Operation;
+    use crate::imageops2::{ImageExt2, Kernel, Normalization};
+    use crate::worker::Worker;
+    use crate::AppState;
+
+    #[test]
+    fn test_worker_delivers_latest() {
+        let image_folder_path = Path::new(&std::env::current_dir().unwrap())
+            .to_path_buf()
+            .join("src/images/1/");
+        let path = image_folder_path.join("Gramado_22k.jpg");
+
+        let (sender, receiver) = mpsc::channel();
+        let worker = Worker::spawn(move |output| sender.send(output).unwrap());
+
+        let mut state = AppState::new();
+        state.selected_image = Some(path.to_string_lossy().into_owned());
+        state.selected_operation = Operation::AdjustBrightness;
+
+        // varios pedidos seguidos, como ao arrastar um slider
+        let mut last = 0;
+        for brightness in [10.0, 20.0, 30.0, 40.0].iter() {
+            state.param1 = *brightness;
+            last = worker.submit(&state);
+        }
+        assert!(worker.is_current(last));
+        assert!(!worker.is_current(last - 1));
+
+        // as geracoes entregues sao crescentes e a ultima e a do pedido mais novo
+        let mut generations = vec![];
+        loop {
+            let output = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+            generations.push(output.generation);
+
+            if output.generation == last {
+                let images = output.images.unwrap().unwrap();
+                let expected = image::open(&path).unwrap().adjust_brightness(40);
+                assert_eq!(*images.result.image, expected);
+                break;
+            }
+        }
+        assert!(generations.windows(2).all(|w| w[0] < w[1]));
+        assert!(generations.len() <= 4);
+
+        state.selected_image = None;
+        let generation = worker.submit(&state);
+        let output = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+        assert_eq!(output.generation, generation);
+        assert!(output.images.is_none());
+
+        // um panic no processamento volta como erro e a thread continua atendendo
+        state.selected_image = Some(path.to_string_lossy().into_owned());
+        let broken = Kernel::Dense {
+            width: 0,
+            height: 1,
+            weights: Arc::new(vec![1.0]),
+        };
+        state.selected_operation = Operation::Convolution(broken, Normalization::Clamp);
+        let generation = worker.submit(&state);
+        let output = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+        assert_eq!(output.generation, generation);
+        assert!(output.images.unwrap().is_err());
+
+        state.selected_operation = Operation::Negative;
+        let generation = worker.submit(&state);
+        let output = receiver.recv_timeout(Duration::from_secs(30)).unwrap();
+        assert_eq!(output.generation, generation);
+        assert!(output.images.unwrap().is_ok());
+    }
+}